@@ -17,14 +17,23 @@
  */
 
 use gtk::{gio, glib, prelude::*, subclass::prelude::*};
-use std::cell::OnceCell;
+use std::{cell::{Cell, OnceCell, RefCell}, path::Path};
+
+use crate::{config::{ExtraCss, Settings}, env};
 
 mod app_context;
 mod app_window;
+mod color_scheme;
 mod list_item;
+mod style;
 
 pub use app_context::AppContext;
+pub use style::{check_stylesheet, load_stylesheet, theme_css, variant_path, Stylesheet};
 use app_window::AppWindow;
+use color_scheme::ColorScheme;
+
+/// Exit code used when a prompt is closed without entering text
+pub const EXIT_CANCELLED: i32 = 130;
 
 glib::wrapper! {
     pub struct App(ObjectSubclass<imp::App>)
@@ -48,22 +57,198 @@ impl App {
     pub fn start(&self) -> glib::ExitCode {
         // Run the application without args to avoid glib complaining
         // about unknown/unexpected args
-        self.run_with_args(&[] as &[&str])
+        let exit_code = self.run_with_args(&[] as &[&str]);
+        if exit_code != glib::ExitCode::SUCCESS {
+            return exit_code;
+        }
+
+        self.imp().exit_code.get()
+    }
+
+    /// Set exit code returned from `start()` after the application quits
+    pub fn set_exit_code(&self, exit_code: glib::ExitCode) {
+        self.imp().exit_code.set(exit_code);
     }
 
     pub fn ctx(&self) -> &AppContext {
         self.imp().ctx.get().unwrap()
     }
+
+    /// Load stylesheet, or the variant of the current color scheme
+    fn load_css(&self) {
+        let stylesheet = self.ctx().get_css_content(self.imp().color_scheme.get());
+        load_stylesheet(self.imp().css_provider.get().unwrap(), &stylesheet);
+    }
+
+    /// Switch stylesheet variant when the desktop color scheme changes. The
+    /// GTK setting is used until the portal settings are read asynchronously,
+    /// so startup doesn't wait on the portal.
+    fn watch_color_scheme(&self) {
+        self.imp().color_scheme.set(color_scheme::gtk_scheme());
+
+        glib::MainContext::default().spawn_local(glib::clone!(@weak self as app => async move {
+            let portal = color_scheme::portal_settings().await;
+
+            color_scheme::connect_changed(portal.as_ref(), glib::clone!(@weak app => move || {
+                app.update_color_scheme();
+            }));
+
+            app.imp().portal.set(portal).unwrap();
+            app.update_color_scheme();
+        }));
+    }
+
+    /// Reload stylesheet when the color scheme differs from the loaded variant
+    fn update_color_scheme(&self) {
+        glib::MainContext::default().spawn_local(glib::clone!(@weak self as app => async move {
+            let portal = app.imp().portal.get().cloned().flatten();
+            let scheme = color_scheme::current(portal.as_ref()).await;
+            if scheme != app.imp().color_scheme.replace(scheme) {
+                glib::g_debug!(env::app_name(), "Color scheme changed, reloading stylesheet");
+                app.load_css();
+            }
+        }));
+    }
+
+    /// Reload stylesheet and settings when the files are modified, replacing
+    /// any monitors of files that were previously watched
+    fn watch_files(&self) {
+        for monitor in self.imp().monitors.take() {
+            monitor.cancel();
+        }
+
+        let config = self.ctx().config.borrow();
+
+        let style_path = config.style_path();
+        let variant_paths = [ColorScheme::Dark, ColorScheme::Light].iter()
+            .filter_map(ColorScheme::variant)
+            .map(|variant| style::variant_path(&style_path, variant));
+
+        for path in std::iter::once(style_path.clone()).chain(variant_paths) {
+            self.watch_file(&path, |app| {
+                glib::g_debug!(env::app_name(), "Reloading stylesheet");
+                app.load_css();
+            });
+        }
+
+        if let Some(ExtraCss::Files(files)) = &config.extra_css {
+            for file_path in files.iter() {
+                self.watch_file(file_path, |app| {
+                    glib::g_debug!(env::app_name(), "Reloading extra css");
+                    app.load_extra_css(&app.ctx().config.borrow());
+                });
+            }
+        }
+
+        // config file along with the files it includes
+        for file_path in Settings::files(&self.ctx().cli.get_config_path()) {
+            self.watch_file(&file_path, |app| {
+                glib::g_debug!(env::app_name(), "Reloading settings");
+                app.reload_settings();
+            });
+        }
+    }
+
+    /// Replace settings with the settings loaded from the config file, and
+    /// re-apply the stylesheets and window settings. The current settings
+    /// are kept when the config file is invalid.
+    fn reload_settings(&self) {
+        let settings = match self.ctx().cli.load_settings() {
+            Ok(settings) => settings,
+            Err(e) => {
+                eprintln!("Error reloading settings: {e}");
+                return;
+            }
+        };
+
+        self.ctx().config.replace(settings);
+
+        let config = self.ctx().config.borrow();
+        self.load_theme(&config);
+        self.load_css();
+        self.load_extra_css(&config);
+        self.windows().iter()
+            .filter_map(|w| w.downcast_ref::<AppWindow>())
+            .for_each(|w| w.apply_settings(&config));
+        drop(config);
+
+        // style, extra_css and include paths may have changed
+        self.watch_files();
+    }
+
+    /// Load bundled theme beneath the stylesheet, or unload it when not set
+    fn load_theme(&self, settings: &Settings) {
+        let provider = self.imp().theme_provider.get().unwrap();
+        match settings.theme {
+            Some(theme) => { load_stylesheet(provider, &Stylesheet::theme(theme)); },
+            None => provider.load_from_string("")
+        }
+    }
+
+    /// Replace providers of `extra_css` setting, each added at a higher
+    /// priority than the last so later files override earlier ones
+    fn load_extra_css(&self, settings: &Settings) {
+        let display = gtk::gdk::Display::default().expect("Could not connect to a display");
+
+        for provider in self.imp().extra_css_providers.take() {
+            gtk::style_context_remove_provider_for_display(&display, &provider);
+        }
+
+        let providers = AppContext::get_extra_css(settings).iter()
+            .enumerate()
+            .map(|(i, stylesheet)| {
+                let provider = gtk::CssProvider::new();
+                load_stylesheet(&provider, stylesheet);
+                gtk::style_context_add_provider_for_display(
+                    &display,
+                    &provider,
+                    gtk::STYLE_PROVIDER_PRIORITY_APPLICATION + 1 + i as u32
+                );
+                provider
+            })
+            .collect();
+
+        self.imp().extra_css_providers.replace(providers);
+    }
+
+    fn watch_file<F: Fn(&Self) + 'static>(&self, file_path: &Path, on_change: F) {
+        let file = gio::File::for_path(file_path);
+        let monitor = match file.monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE) {
+            Ok(monitor) => monitor,
+            Err(e) => {
+                glib::g_debug!(env::app_name(), "Unable to watch {}: {e}", file_path.to_string_lossy());
+                return;
+            }
+        };
+
+        monitor.connect_changed(glib::clone!(@weak self as app => move |_, _, _, event| {
+            if matches!(event,
+                gio::FileMonitorEvent::ChangesDoneHint |
+                gio::FileMonitorEvent::Created |
+                gio::FileMonitorEvent::Deleted
+            ) {
+                on_change(&app);
+            }
+        }));
+
+        self.imp().monitors.borrow_mut().push(monitor);
+    }
 }
 
 mod imp {
     use super::*;
 
-    #[derive(Default)]
     // #[derive(Default, glib::Properties)]
     // #[properties(wrapper_type = super::App)]
     pub struct App {
-        pub ctx: OnceCell<AppContext>
+        pub ctx: OnceCell<AppContext>,
+        pub exit_code: Cell<glib::ExitCode>,
+        pub css_provider: OnceCell<gtk::CssProvider>,
+        pub theme_provider: OnceCell<gtk::CssProvider>,
+        pub extra_css_providers: RefCell<Vec<gtk::CssProvider>>,
+        pub portal: OnceCell<Option<gio::DBusProxy>>,
+        pub color_scheme: Cell<ColorScheme>,
+        pub monitors: RefCell<Vec<gio::FileMonitor>>
     }
 
     #[glib::object_subclass]
@@ -71,6 +256,19 @@ mod imp {
         const NAME: &'static str = "App";
         type ParentType = gtk::Application;
         type Type = super::App;
+
+        fn new() -> Self {
+            Self {
+                ctx: OnceCell::default(),
+                exit_code: glib::ExitCode::SUCCESS.into(),
+                css_provider: OnceCell::default(),
+                theme_provider: OnceCell::default(),
+                extra_css_providers: RefCell::default(),
+                portal: OnceCell::default(),
+                color_scheme: Cell::new(ColorScheme::Default),
+                monitors: RefCell::default()
+            }
+        }
     }
 
     // #[glib::derived_properties]
@@ -94,23 +292,38 @@ mod imp {
         fn startup(&self) {
             self.parent_startup();
 
-            let css = self.obj().ctx().get_css_content();
-            load_css_content(css.as_str());
+            self.obj().watch_color_scheme();
+
+            let theme_provider = gtk::CssProvider::new();
+            gtk::style_context_add_provider_for_display(
+                &gtk::gdk::Display::default().expect("Could not connect to a display"),
+                &theme_provider,
+                gtk::STYLE_PROVIDER_PRIORITY_APPLICATION - 1,
+            );
+            self.theme_provider.set(theme_provider).unwrap();
+            self.obj().load_theme(&self.obj().ctx().config.borrow());
+
+            let provider = gtk::CssProvider::new();
+            let stylesheet = self.obj().ctx().get_css_content(self.color_scheme.get());
+            load_css_content(&provider, &stylesheet);
+            self.css_provider.set(provider).unwrap();
+            self.obj().load_extra_css(&self.obj().ctx().config.borrow());
+
+            self.obj().watch_files();
         }
     }
 
     impl GtkApplicationImpl for App {}
 }
 
-fn load_css_content(css: &str) {
+fn load_css_content(provider: &gtk::CssProvider, stylesheet: &Stylesheet) {
     // Load the CSS file and add it to the provider
-    let provider = gtk::CssProvider::new();
-    provider.load_from_string(css);
+    load_stylesheet(provider, stylesheet);
 
     // Add the provider to the default screen
     gtk::style_context_add_provider_for_display(
         &gtk::gdk::Display::default().expect("Could not connect to a display"),
-        &provider,
+        provider,
         gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
     );
 }
@@ -16,23 +16,93 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use gtk::glib;
-use std::{fs::{self, File}, io::{self, BufReader}, path::PathBuf};
-use crate::{cli::Cli, config::Settings, env};
-use super::list_item::ListItemObject;
+use gtk::{gio, glib, prelude::*};
+use std::{cell::RefCell, collections::HashSet, fs::File, io::{self, BufReader}, path::{Path, PathBuf}};
+use crate::{cli::{Cli, Commands}, config::Settings, env};
+use super::{color_scheme::ColorScheme, list_item::{self, ListItemObject}, style::{self, Stylesheet}};
 
 
 pub struct AppContext {
     pub cli: Cli,
-    pub config: Settings,
-    pub list_items: Vec<ListItemObject>
+    /// Settings, replaced when the config file changes
+    pub config: RefCell<Settings>,
+    pub list_items: Vec<ListItemObject>,
+    /// URIs passed to the launched application
+    pub launch_uris: Vec<String>,
+    /// Content types the launched application is set as the default for
+    pub default_for_types: Vec<String>
 }
 
 impl AppContext {
     pub fn with_app_list(cli: Cli) -> io::Result<Self> {
         let config = cli.load_settings()?;
-        let list_items = ListItemObject::app_list(config.history_size)?;
-        Ok(Self { cli, config, list_items })
+
+        let files = match &cli.command {
+            Commands::Launcher { files } => files.clone(),
+            _ => vec![]
+        };
+
+        let list_items = if files.is_empty() {
+            ListItemObject::app_list(&config, None)?
+        } else {
+            let handlers = file_handlers(&files)?;
+            ListItemObject::app_list(&config, Some(&|app_info| {
+                app_info.id().is_some_and(|id| handlers.contains(id.as_str()))
+            }))?
+        };
+
+        let launch_uris = uris_from_paths(&files);
+
+        Ok(Self { cli, config: config.into(), list_items, launch_uris, default_for_types: vec![] })
+    }
+
+    /// Applications for the content types of files, recommended applications
+    /// first followed by other applications that can open the files
+    pub fn with_open_with(cli: Cli, files: Vec<PathBuf>, set_default: bool) -> io::Result<Self> {
+        let config = cli.load_settings()?;
+
+        let content_types = files.iter()
+            .map(|path| content_type(path))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let handlers = file_handlers(&files)?;
+        let is_handler = |app_info: &gio::AppInfo| app_info.id()
+            .is_some_and(|id| handlers.contains(id.as_str()));
+
+        // recommended/fallback of the first file, limited to apps that handle every file
+        let content_type = content_types.first().map(String::as_str).unwrap_or_default();
+        let recommended: Vec<_> = gio::AppInfo::recommended_for_type(content_type).iter()
+            .filter(|a| is_handler(a))
+            .map(ListItemObject::from)
+            .collect();
+        let fallback: Vec<_> = gio::AppInfo::fallback_for_type(content_type).iter()
+            .filter(|a| is_handler(a))
+            .map(ListItemObject::from)
+            .collect();
+
+        if recommended.is_empty() && fallback.is_empty() {
+            let paths: Vec<_> = files.iter().map(|path| path.to_string_lossy()).collect();
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No applications can open {}", paths.join(", "))
+            ));
+        }
+
+        let mut list_items = Vec::new();
+        if !recommended.is_empty() && !fallback.is_empty() {
+            list_items.push(ListItemObject::header("Recommended applications"));
+            list_items.extend(recommended);
+            list_items.push(ListItemObject::header("Other applications"));
+            list_items.extend(fallback);
+        } else {
+            list_items.extend(recommended);
+            list_items.extend(fallback);
+        }
+
+        let launch_uris = uris_from_paths(&files);
+        let default_for_types = if set_default { content_types } else { vec![] };
+
+        Ok(Self { cli, config: config.into(), list_items, launch_uris, default_for_types })
     }
 
     pub fn with_menu_list(cli: Cli, file_path: Option<PathBuf>) -> io::Result<Self> {
@@ -47,22 +117,91 @@ impl AppContext {
 
         let config = cli.load_settings()?;
 
-        Ok(Self { cli, config, list_items })
+        Ok(Self { cli, config: config.into(), list_items, launch_uris: vec![], default_for_types: vec![] })
+    }
+
+    pub fn with_prompt(cli: Cli) -> io::Result<Self> {
+        let config = cli.load_settings()?;
+        Ok(Self { cli, config: config.into(), list_items: vec![], launch_uris: vec![], default_for_types: vec![] })
     }
 
     pub fn get_window_size(&self) -> (i32, i32) {
-        (self.config.width, self.config.height)
+        let config = self.config.borrow();
+        (config.width, config.height)
     }
 
-    pub fn get_css_content(&self) -> String {
-        let css_path = self.cli.get_style_path();
+    /// Stylesheet variant for color scheme when it exists, e.g. `style-dark.css`,
+    /// otherwise the configured stylesheet
+    pub fn get_css_content(&self, scheme: ColorScheme) -> Stylesheet {
+        let config = self.config.borrow();
+        let css_path = config.style_path();
+        let css_path = scheme.variant()
+            .map(|variant| style::variant_path(&css_path, variant))
+            .filter(|path| path.is_file())
+            .unwrap_or(css_path);
 
-        match fs::read_to_string(&css_path) {
-            Ok(css) => css,
+        match Stylesheet::from_file(&css_path) {
+            Ok(stylesheet) => stylesheet,
             Err(..) => {
-                glib::g_debug!(env::app_name(), "Unable to load {}, using builtin style", css_path.to_string_lossy());
-                include_str!("../../assets/style.css").to_owned()
+                let theme = config.theme.unwrap_or_default();
+                glib::g_debug!(env::app_name(), "Unable to load {}, using {theme} theme", css_path.to_string_lossy());
+                Stylesheet::theme(theme)
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Stylesheets of `extra_css` setting, skipping files that can't be read
+    pub fn get_extra_css(config: &Settings) -> Vec<Stylesheet> {
+        let Some(extra_css) = &config.extra_css else {
+            return vec![];
+        };
+
+        Stylesheet::from_extra_css(extra_css).into_iter()
+            .filter_map(|stylesheet| match stylesheet {
+                Ok(stylesheet) => Some(stylesheet),
+                Err(e) => {
+                    eprintln!("Unable to load extra css {e}");
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+fn uris_from_paths(files: &[PathBuf]) -> Vec<String> {
+    files.iter()
+        .map(|path| list_item::uri_from_arg(path.to_string_lossy().as_ref()))
+        .collect()
+}
+
+/// Content type of file
+fn content_type(file_path: &Path) -> io::Result<String> {
+    gio::File::for_path(file_path)
+        .query_info(gio::FILE_ATTRIBUTE_STANDARD_CONTENT_TYPE, gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE)
+        .ok()
+        .and_then(|info| info.content_type())
+        .map(String::from)
+        .ok_or_else(|| io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}: unable to determine content type", file_path.to_string_lossy())
+        ))
+}
+
+/// Ids of applications that handle the content type of every file
+fn file_handlers(files: &[PathBuf]) -> io::Result<HashSet<String>> {
+    let mut handlers: Option<HashSet<String>> = None;
+
+    for file_path in files.iter() {
+        let ids: HashSet<String> = gio::AppInfo::all_for_type(content_type(file_path)?.as_str()).iter()
+            .filter_map(|app_info| app_info.id())
+            .map(String::from)
+            .collect();
+
+        handlers = Some(match handlers {
+            Some(handlers) => handlers.intersection(&ids).cloned().collect(),
+            None => ids
+        });
+    }
+
+    Ok(handlers.unwrap_or_default())
+}
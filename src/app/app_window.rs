@@ -18,9 +18,10 @@
 
 use gtk::{gio, glib::{self, prelude::*}, prelude::*, subclass::prelude::*};
 use gtk4_layer_shell::{KeyboardMode, Layer, LayerShell};
-use std::cell::{Cell, RefCell};
+use std::cell::{Cell, OnceCell, RefCell};
 
-use super::{App, list_item::ListItemObject};
+use crate::{cli::Commands, config::{CaptureTo, ItemKind, Layout, Settings}, env};
+use super::{App, EXIT_CANCELLED, list_item::{self, ListItemObject}};
 
 
 glib::wrapper! {
@@ -34,6 +35,7 @@ glib::wrapper! {
 impl AppWindow {
     pub fn new(app: &App) -> Self {
         let ctx = app.ctx();
+        let config = ctx.config.borrow();
 
         let (def_width, def_height) = ctx.get_window_size();
 
@@ -41,21 +43,49 @@ impl AppWindow {
             .item_type(ListItemObject::static_type())
             .build();
 
-        ctx.list_items.iter()
-            .for_each(|i| items.append(i));
+        set_section_indices(&ctx.list_items);
+        items.extend_from_slice(&ctx.list_items);
 
-        let list_model = new_list_model(items);
-        let orientation: gtk::Orientation = ctx.config.orientation.into();
+        let description_filter = gtk::BoolFilter::new(Some(gtk::ConstantExpression::new(true)));
+        description_filter.set_invert(config.hide_description);
 
-        glib::Object::builder()
+        let list_model = new_list_model(&items, &description_filter);
+        let orientation: gtk::Orientation = config.orientation.into();
+        let (min_columns, max_columns) = config.grid_columns();
+        // GtkImage falls back to icon-size when pixel-size is -1
+        let icon_size = config.icon_size.unwrap_or(-1);
+
+        let (prompt, password, placeholder) = match &ctx.cli.command {
+            Commands::Prompt { password, placeholder } => (true, *password, placeholder.clone()),
+            _ => (false, false, None)
+        };
+
+        if prompt {
+            // closing the window without entering text cancels the prompt
+            app.set_exit_code(EXIT_CANCELLED.into());
+        }
+
+        let win: Self = glib::Object::builder()
             .property("application", app)
             .property("name", "window")
             .property("default-width", def_width)
             .property("default-height", def_height)
             .property("list-model", list_model)
             .property("orientation", orientation)
-            .property("show-search", !ctx.config.hide_search)
-            .build()
+            .property("grid", config.layout == Layout::Grid)
+            .property("min-columns", min_columns)
+            .property("max-columns", max_columns)
+            .property("icon-size", icon_size)
+            .property("show-description", !config.hide_description)
+            .property("show-search", show_search(&config, prompt, password))
+            .property("show-list", !prompt)
+            .property("password", password)
+            .property("placeholder", placeholder)
+            .property("search-args", matches!(ctx.cli.command, Commands::Launcher { .. }))
+            .build();
+
+        win.imp().description_filter.set(description_filter).unwrap();
+        win
     }
 
     fn app(&self) -> App {
@@ -73,10 +103,45 @@ impl AppWindow {
         self.set_layer(Layer::Top);
     }
 
-    fn setup_list(&self) {
+    /// Re-apply settings that can change while the window is open. The layout
+    /// is fixed once the list or grid view is created.
+    pub fn apply_settings(&self, settings: &Settings) {
+        // default size only applies before the window is mapped, the size
+        // request resizes the mapped layer surface
+        self.set_default_size(settings.width, settings.height);
+        self.set_size_request(settings.width, settings.height);
+
+        self.set_orientation(gtk::Orientation::from(settings.orientation));
+        self.set_icon_size(settings.icon_size.unwrap_or(-1));
+        self.set_show_description(!settings.hide_description);
+        self.set_search_description(!settings.hide_description);
+        self.set_show_search(show_search(settings, !self.show_list(), self.password()));
+
+        // list item factory captures orientation, icon size, etc
+        let factory = self.new_factory();
+        match self.imp().grid.get() {
+            Some(grid) => {
+                grid.set_orientation(self.orientation());
+                grid.set_factory(Some(&factory));
+            },
+            None => self.imp().list.set_factory(Some(&factory))
+        }
+
+        self.view().set_can_focus(!self.show_search());
+    }
+
+    /// Include descriptions in the search, or search only labels and executables
+    fn set_search_description(&self, search_description: bool) {
+        if let Some(filter) = self.imp().description_filter.get() {
+            filter.set_invert(!search_description);
+        }
+    }
+
+    fn new_factory(&self) -> gtk::BuilderListItemFactory {
         // invert orientation applied to list items
-        // i.e. icon above label (vertical) when list is horizontal
+        // i.e. icon above label (vertical) when list is horizontal or a grid
         let orientation = match self.orientation() {
+            _ if self.grid() => gtk::Orientation::Vertical,
             gtk::Orientation::Horizontal => gtk::Orientation::Vertical,
             _ => gtk::Orientation::Horizontal
         };
@@ -84,33 +149,272 @@ impl AppWindow {
         let scope = gtk::BuilderRustScope::new();
         scope.add_callback("get_orientation", move |_| Some(orientation.to_value()));
 
+        let icon_size = self.icon_size();
+        scope.add_callback("get_icon_size", move |_| Some(icon_size.to_value()));
+
+        // left align labels beside icon, center align labels below icon
+        let xalign: f32 = match orientation {
+            gtk::Orientation::Horizontal => 0.0,
+            _ => 0.5
+        };
+        scope.add_callback("get_label_xalign", move |_| Some(xalign.to_value()));
+
+        // args are the closure "this" object followed by the description
+        let show_description = self.show_description();
+        for (name, kind) in [
+            ("is_item", ItemKind::Item),
+            ("is_header", ItemKind::Header),
+            ("is_separator", ItemKind::Separator)
+        ] {
+            scope.add_callback(name, move |args| {
+                let is_kind = args.get(1)
+                    .and_then(|v| v.get::<ItemKind>().ok())
+                    .is_some_and(|k| k == kind);
+                Some(is_kind.to_value())
+            });
+        }
+
+        scope.add_callback("show_description", move |args| {
+            let description = args.get(1)
+                .and_then(|v| v.get::<Option<String>>().ok())
+                .flatten()
+                .unwrap_or_default();
+            Some((show_description && !description.is_empty()).to_value())
+        });
+
         let template = include_bytes!("../../assets/ui/list_item.ui");
-        let factory = gtk::BuilderListItemFactory::from_bytes(
+        gtk::BuilderListItemFactory::from_bytes(
             Some(&scope),
             &glib::Bytes::from_static(template)
-        );
+        )
+    }
+
+    fn setup_list(&self) {
+        let factory = self.new_factory();
+
+        if self.grid() {
+            // grid view replaces the list view from the window template
+            let grid = gtk::GridView::builder()
+                .name("list")
+                .orientation(self.orientation())
+                .min_columns(self.min_columns())
+                .max_columns(self.max_columns())
+                .single_click_activate(true)
+                .can_focus(false)
+                .model(&self.list_model())
+                .factory(&factory)
+                .build();
 
-        self.imp().list.set_factory(Some(&factory));
+            grid.connect_activate(glib::clone!(@weak self as win => move |_, _| {
+                win.on_list_activate();
+            }));
+
+            self.imp().scroll.set_child(Some(&grid));
+            self.imp().grid.set(grid).unwrap();
+        } else {
+            self.imp().list.set_factory(Some(&factory));
+        }
 
         // search field delegates "activate" signal to list when enter is pressed
         // when search is hidden, we let list field have focus so "activate" works
         if !self.show_search() {
-            self.imp().list.set_can_focus(true);
+            self.view().set_can_focus(true);
+        }
+    }
+
+    /// Get the list or grid view displaying the list model
+    fn view(&self) -> gtk::ListBase {
+        match self.imp().grid.get() {
+            Some(grid) => grid.clone().upcast(),
+            None => self.imp().list.get().upcast()
         }
     }
 
+    fn scroll_to(&self, position: u32) {
+        match self.imp().grid.get() {
+            Some(grid) => grid.scroll_to(position, gtk::ListScrollFlags::SELECT, None),
+            None => self.imp().list.scroll_to(position, gtk::ListScrollFlags::SELECT, None)
+        }
+    }
+
+    /// Number of columns currently displayed in the grid
+    fn grid_columns(&self) -> u32 {
+        let (min, max) = (self.min_columns(), self.max_columns().max(self.min_columns()));
+        if min == max {
+            return min;
+        }
+
+        // GridView doesn't expose the number of columns, count the children
+        // allocated in the same line (row, or column of a horizontal grid)
+        // as the first child
+        let grid = self.view();
+        let horizontal = self.orientation() == gtk::Orientation::Horizontal;
+        let line_offset = |child: &gtk::Widget| child.compute_bounds(&grid)
+            .map(|bounds| if horizontal { bounds.x() } else { bounds.y() });
+
+        let children: Vec<_> = std::iter::successors(grid.first_child(), |child| child.next_sibling())
+            .filter(|child| child.is_visible())
+            .collect();
+
+        let columns = match children.first().and_then(line_offset) {
+            Some(first) => children.iter()
+                .filter(|child| line_offset(child).is_some_and(|offset| (offset - first).abs() < 1.0))
+                .count() as u32,
+            None => 1
+        };
+
+        columns.clamp(min, max)
+    }
+
+    /// Number of positions to move the selection for `key_name`
+    fn selection_step(&self, key_name: &str) -> Option<i64> {
+        let grid = self.grid();
+        let line = if grid { self.grid_columns() as i64 } else { 1 };
+        // items flow top to bottom in a horizontal grid
+        let flipped = grid && self.orientation() == gtk::Orientation::Horizontal;
+
+        match key_name {
+            "Tab" => Some(1),
+            "ISO_Left_Tab" => Some(-1),
+            "Down" if flipped => Some(1),
+            "Up" if flipped => Some(-1),
+            "Down" => Some(line),
+            "Up" => Some(-line),
+            "Right" if flipped => Some(line),
+            "Left" if flipped => Some(-line),
+            "Right" if grid => Some(1),
+            "Left" if grid => Some(-1),
+            _ => None
+        }
+    }
+
+    /// Move selection for navigation key, returns false when key is not handled
+    fn move_selection(&self, key_name: &str) -> bool {
+        let Some(step) = self.selection_step(key_name) else {
+            return false;
+        };
+
+        let model = self.list_model();
+        let in_range = |i: i64| i >= 0 && i < model.n_items() as i64;
+
+        // continue past headers and separators in the same direction
+        let mut i = model.selected() as i64 + step;
+        while in_range(i) && !is_selectable(&model, i as u32) {
+            i += step.signum();
+        }
+
+        if in_range(i) {
+            self.scroll_to(i as u32);
+        }
+
+        true
+    }
+
     #[template_callback]
     fn on_list_activate(&self) {
-        let item = self.list_model().selected_item();
-        let item = item
-            .and_downcast_ref::<ListItemObject>()
-            .expect("ListItemObject");
+        // search field doubles as the text entry in prompt mode
+        if !self.show_list() {
+            self.on_prompt_activate();
+            return;
+        }
 
-        item.launch(self.app().ctx().config.history_size);
+        // nothing is selected when the list is empty
+        let Some(item) = self.list_model().selected_item().and_downcast::<ListItemObject>() else {
+            return;
+        };
+
+        if !item.is_selectable() || self.busy() {
+            return;
+        }
+
+        if let Some(capture_to) = item.capture() {
+            self.launch_capture(item.clone(), capture_to);
+            return;
+        }
+
+        // files from the command line, followed by arguments from the search
+        let app = self.app();
+        let ctx = app.ctx();
+        let uris: Vec<_> = ctx.launch_uris.iter().cloned()
+            .chain(self.imp().search_args.borrow().iter().map(|arg| list_item::uri_from_arg(&env::expand_home(arg))))
+            .collect();
+
+        // launch before closing, activation tokens are only granted to the focused window
+        let result = item.launch(&ctx.config.borrow(), &uris)
+            .and_then(|_| item.set_default_for_types(&ctx.default_for_types));
+
+        if let Err(e) = result {
+            eprintln!("Error: {e}");
+            self.app().set_exit_code(glib::ExitCode::FAILURE);
+        }
 
         self.close();
     }
 
+    /// Run command of item in the background, while the spinner is displayed,
+    /// then display or print the output
+    fn launch_capture(&self, item: ListItemObject, capture_to: CaptureTo) {
+        self.set_busy(true);
+
+        glib::MainContext::default().spawn_local(glib::clone!(@weak self as win => async move {
+            let output = item.launch_capture().await;
+            win.set_busy(false);
+
+            match (output, capture_to) {
+                (Ok(output), CaptureTo::List) => {
+                    win.set_items(&ListItemObject::capture_list(&output));
+                },
+                (Ok(output), CaptureTo::Stdout) => {
+                    print!("{}", output.stdout);
+                    if let Some(error) = output.error() {
+                        eprint!("{}", output.stderr);
+                        eprintln!("Error: {error}");
+                        win.app().set_exit_code(glib::ExitCode::FAILURE);
+                    }
+                    win.close();
+                },
+                (Err(e), _) => {
+                    eprintln!("Error: {e}");
+                    win.app().set_exit_code(glib::ExitCode::FAILURE);
+                    win.close();
+                }
+            }
+        }));
+    }
+
+    /// Replace the items of the list, and clear the search
+    fn set_items(&self, list_items: &[ListItemObject]) {
+        set_section_indices(list_items);
+
+        let items = self.list_model()
+            .model()
+            .and_downcast::<gtk::SortListModel>()
+            .and_then(|m| m.model())
+            .and_downcast::<gtk::FilterListModel>()
+            .and_then(|m| m.model())
+            .and_downcast::<gio::ListStore>()
+            .expect("gio::ListStore");
+
+        items.splice(0, items.n_items(), list_items);
+
+        self.imp().search.set_text("");
+        skip_unselectable(&self.list_model());
+    }
+
+    #[template_callback]
+    fn on_prompt_activate(&self) {
+        let text = if self.password() {
+            self.imp().password.text()
+        } else {
+            self.imp().search.text()
+        };
+
+        println!("{text}");
+
+        self.app().set_exit_code(glib::ExitCode::SUCCESS);
+        self.close();
+    }
+
     #[template_callback]
     fn on_key_pressed(&self,
         keyval: gtk::gdk::Key,
@@ -122,21 +426,8 @@ impl AppWindow {
         // can-focus = false and add this key handler routine
 
         if let Some(key_name) = keyval.name() {
-            let model = self.list_model();
-            let list = self.imp().list.get();
-
-            if model.n_items() > 0 {
-                if key_name == "Down" || key_name == "Tab" {
-                    let i = model.selected();
-                    if i < (model.n_items() - 1) {
-                        list.scroll_to(i+1, gtk::ListScrollFlags::SELECT, None);
-                    }
-                } else if key_name == "Up" || key_name == "ISO_Left_Tab" {
-                    let i = model.selected();
-                    if i > 0 {
-                        list.scroll_to(i-1, gtk::ListScrollFlags::SELECT, None);
-                    }
-                }
+            if self.list_model().n_items() > 0 {
+                self.move_selection(key_name.as_str());
             }
         }
 
@@ -154,39 +445,101 @@ mod imp {
         #[template_child]
         pub list: gtk::TemplateChild<gtk::ListView>,
 
+        #[template_child]
+        pub scroll: gtk::TemplateChild<gtk::ScrolledWindow>,
+
         #[template_child]
         pub search: gtk::TemplateChild<gtk::SearchEntry>,
 
+        #[template_child]
+        pub password: gtk::TemplateChild<gtk::PasswordEntry>,
+
         // I don't know why, but the values set for properties in AppWindow::new()
         // are not available in constructed method, unless `construct_only` is set
+        // (or `construct` for properties that are re-applied when settings change)
 
         #[property(name = "list-model", get, set, construct_only)]
         pub list_model: RefCell<gtk::SingleSelection>,
 
-        #[property(get, set, construct_only, builder(gtk::Orientation::Vertical))]
+        #[property(get, set, construct, builder(gtk::Orientation::Vertical))]
         pub orientation: Cell<gtk::Orientation>,
 
-        #[property(name = "show-search", get, set, construct_only)]
+        #[property(name = "grid", get, set, construct_only)]
+        pub grid_layout: Cell<bool>,
+
+        #[property(name = "min-columns", get, set, construct_only)]
+        pub min_columns: Cell<u32>,
+
+        #[property(name = "max-columns", get, set, construct_only)]
+        pub max_columns: Cell<u32>,
+
+        pub grid: OnceCell<gtk::GridView>,
+
+        #[property(name = "icon-size", get, set, construct)]
+        pub icon_size: Cell<i32>,
+
+        #[property(name = "show-search", get, set, construct)]
         pub show_search: Cell<bool>,
 
+        #[property(name = "show-description", get, set, construct)]
+        pub show_description: Cell<bool>,
+
+        #[property(name = "show-list", get, set, construct_only)]
+        pub show_list: Cell<bool>,
+
+        #[property(name = "password", get, set, construct_only)]
+        pub password_mode: Cell<bool>,
+
+        #[property(get, set, construct_only)]
+        pub placeholder: RefCell<Option<String>>,
+
+        #[property(name = "search-args", get, set, construct_only)]
+        pub search_args_enabled: Cell<bool>,
+
+        /// Arguments following the app name in the search text
+        pub search_args: RefCell<Vec<String>>,
+
+        /// Matches every item when searching by description, otherwise none
+        pub description_filter: OnceCell<gtk::BoolFilter>,
+
+        /// True while waiting for output of a command
+        #[property(get, set)]
+        pub busy: Cell<bool>,
+
         #[property(set = Self::set_search_filter)]
         pub search_filter: RefCell<String>
     }
 
     impl AppWindow {
         fn set_search_filter(&self, search: &glib::Value) {
-            let filter = self.list_model.borrow()
+            let sort_model = self.list_model.borrow()
+                .model()
+                .and_downcast::<gtk::SortListModel>()
+                .expect("gtk::SortListModel");
+
+            let filter = sort_model
                 .model()
                 .and_downcast::<gtk::FilterListModel>()
                 .expect("gtk::FilterListModel")
                 .filter()
-                .and_downcast::<gtk::AnyFilter>()
-                .expect("gtk::AnyFilter");
-
-            for str_filter in filter.iter::<glib::Object>() {
-                str_filter.unwrap()
-                    .set_property("search", search);
-            }
+                .expect("gtk::Filter");
+
+            let search = match search.get::<String>() {
+                Ok(text) if self.search_args_enabled.get() => {
+                    let (query, args) = split_search_args(text.as_str())
+                        .unwrap_or((text.as_str(), vec![]));
+                    self.search_args.replace(args);
+                    query.to_value()
+                },
+                _ => search.clone()
+            };
+
+            set_filter_search(&filter, &search);
+
+            // re-rank description matches for the new search text
+            sort_model.sorter()
+                .expect("gtk::Sorter")
+                .changed(gtk::SorterChange::Different);
         }
     }
 
@@ -209,10 +562,25 @@ mod imp {
         fn new() -> Self {
             Self {
                 list: TemplateChild::default(),
+                scroll: TemplateChild::default(),
                 search: TemplateChild::default(),
+                password: TemplateChild::default(),
                 list_model: RefCell::default(),
                 orientation: gtk::Orientation::Vertical.into(),
+                grid_layout: false.into(),
+                min_columns: 1.into(),
+                max_columns: 7.into(),
+                grid: OnceCell::default(),
+                icon_size: (-1).into(),
                 show_search: true.into(),
+                show_description: true.into(),
+                show_list: true.into(),
+                password_mode: false.into(),
+                placeholder: RefCell::default(),
+                search_args_enabled: false.into(),
+                search_args: RefCell::default(),
+                description_filter: OnceCell::default(),
+                busy: false.into(),
                 search_filter: "".to_string().into()
             }
         }
@@ -234,8 +602,35 @@ mod imp {
                 .sync_create()
                 .build();
 
+            // selection model selects the first item when the list changes,
+            // move it past any headers or separators
+            let list_model = win.list_model();
+            list_model.connect_selected_notify(skip_unselectable);
+            skip_unselectable(&list_model);
+
             // send key events to search when key pressed on list
-            self.search.set_key_capture_widget(Some(&self.list.get()));
+            self.search.set_key_capture_widget(Some(&win.view()));
+
+            if win.grid() && win.show_list() {
+                // left/right navigate the grid instead of the search text cursor,
+                // unless there is search text to move the cursor within
+                let controller = gtk::EventControllerKey::new();
+                controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+                controller.connect_key_pressed(glib::clone!(@weak win => @default-return glib::Propagation::Proceed, move |_, keyval, _, _| {
+                    let search = &win.imp().search;
+                    let editing = !search.text().is_empty()
+                        && RootExt::focus(&win).is_some_and(|focus| focus.is_ancestor(&**search));
+
+                    match keyval.name() {
+                        Some(name) if !editing && (name == "Left" || name == "Right") => {
+                            win.move_selection(name.as_str());
+                            glib::Propagation::Stop
+                        },
+                        _ => glib::Propagation::Proceed
+                    }
+                }));
+                win.add_controller(controller);
+            }
         }
     }
 
@@ -244,43 +639,152 @@ mod imp {
     impl ApplicationWindowImpl for AppWindow {}
 }
 
-fn new_list_model(items: impl IsA<gtk::gio::ListModel>) -> gtk::SingleSelection {
-    let label_prop_expr = gtk::PropertyExpression::new(
-        ListItemObject::static_type(),
-        gtk::Expression::NONE,
-        "label"
-    );
+/// Split search text into the app name and arguments, when the text following
+/// the first word is URIs or file paths, e.g. "code ~/src/proj"
+fn split_search_args(text: &str) -> Option<(&str, Vec<String>)> {
+    let (query, rest) = text.trim_start().split_once(char::is_whitespace)?;
 
-    let exec_prop_expr = gtk::PropertyExpression::new(
-        ListItemObject::static_type(),
-        gtk::Expression::NONE,
-        "executable"
-    );
+    let args: Vec<String> = glib::shell_parse_argv(rest.trim()).ok()?.iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
 
-    let filter = gtk::AnyFilter::new();
+    let is_uri_or_path = |arg: &String| arg.contains("://") || arg.starts_with(['/', '~', '.']);
+    if args.is_empty() || !args.iter().all(is_uri_or_path) {
+        return None;
+    }
 
-    filter.append(
-        gtk::StringFilter::builder()
-            .match_mode(gtk::StringFilterMatchMode::Substring)
-            .ignore_case(true)
-            .expression(label_prop_expr)
-            .build()
-    );
+    Some((query, args))
+}
 
-    filter.append(
-        gtk::StringFilter::builder()
-            .match_mode(gtk::StringFilterMatchMode::Substring)
-            .ignore_case(true)
-            .expression(exec_prop_expr)
-            .build()
-    );
+/// Headers and separators each begin a new section of the list
+fn set_section_indices(items: &[ListItemObject]) {
+    let mut section = 0;
+    for item in items.iter() {
+        if !item.is_selectable() {
+            section += 1;
+        }
+        item.set_section_index(section);
+    }
+}
+
+/// Search is always shown for text prompts, and replaced by password entry
+fn show_search(settings: &Settings, prompt: bool, password: bool) -> bool {
+    !password && (prompt || !settings.hide_search)
+}
+
+/// List model filtered by the search text, where descriptions are only
+/// searched while `description_filter` matches
+fn new_list_model(items: &gio::ListStore, description_filter: &gtk::BoolFilter) -> gtk::SingleSelection {
+    let label_filter = new_string_filter("label");
+    let exec_filter = new_string_filter("executable");
+
+    let search_description = gtk::EveryFilter::new();
+    search_description.append(description_filter.clone());
+    search_description.append(new_string_filter("description"));
+
+    let search_filter = gtk::AnyFilter::new();
+    search_filter.append(label_filter.clone());
+    search_filter.append(exec_filter.clone());
+    search_filter.append(search_description);
+
+    let item_filter = gtk::EveryFilter::new();
+    item_filter.append(gtk::CustomFilter::new(|obj| {
+        obj.downcast_ref::<ListItemObject>()
+            .is_some_and(|item| item.is_selectable())
+    }));
+    item_filter.append(search_filter);
+
+    // while searching, separators are hidden and headers are only
+    // displayed when one of the items in their section is a match
+    let section_filter = gtk::CustomFilter::new(glib::clone!(
+        @strong items, @strong item_filter, @strong label_filter => move |obj| {
+            let item = obj.downcast_ref::<ListItemObject>().expect("ListItemObject");
+            let searching = label_filter.search().is_some_and(|s| !s.is_empty());
+
+            match item.kind() {
+                ItemKind::Item => false,
+                ItemKind::Separator => !searching,
+                ItemKind::Header => !searching || items.iter::<ListItemObject>()
+                    .flatten()
+                    .any(|i| i.section_index() == item.section_index() && item_filter.match_(&i))
+            }
+        }
+    ));
+
+    item_filter.connect_changed(glib::clone!(@weak section_filter => move |_, _| {
+        section_filter.changed(gtk::FilterChange::Different);
+    }));
+
+    let filter = gtk::AnyFilter::new();
+    filter.append(item_filter);
+    filter.append(section_filter);
 
     let filter_model = gtk::FilterListModel::new(
-        Some(items),
+        Some(items.clone()),
         Some(filter)
     );
 
+    // items only matching by description are ranked below label/executable
+    // matches within their section, otherwise the sort is stable and the
+    // original order of items is retained
+    let sorter = gtk::CustomSorter::new(move |a, b| {
+        let key = |obj: &glib::Object| {
+            let item = obj.downcast_ref::<ListItemObject>().expect("ListItemObject");
+            let rank = !(label_filter.match_(obj) || exec_filter.match_(obj));
+            (item.section_index(), item.is_selectable(), rank)
+        };
+        key(a).cmp(&key(b)).into()
+    });
+
+    let sort_model = gtk::SortListModel::new(
+        Some(filter_model),
+        Some(sorter)
+    );
+
     gtk::SingleSelection::builder()
-        .model(&filter_model)
+        .model(&sort_model)
+        .build()
+}
+
+/// Set search text of all string filters in `filter` and any nested filters
+fn set_filter_search(filter: &gtk::Filter, search: &glib::Value) {
+    if let Some(multi_filter) = filter.downcast_ref::<gtk::MultiFilter>() {
+        for child in multi_filter.iter::<gtk::Filter>() {
+            set_filter_search(&child.unwrap(), search);
+        }
+    } else if filter.is::<gtk::StringFilter>() {
+        filter.set_property("search", search);
+    }
+}
+
+fn is_selectable(model: &gtk::SingleSelection, position: u32) -> bool {
+    model.item(position)
+        .and_downcast::<ListItemObject>()
+        .is_some_and(|item| item.is_selectable())
+}
+
+/// Select the next selectable item when a header or separator is selected
+fn skip_unselectable(model: &gtk::SingleSelection) {
+    let selected = model.selected();
+    if selected >= model.n_items() || is_selectable(model, selected) {
+        return;
+    }
+
+    if let Some(i) = (selected..model.n_items()).find(|&i| is_selectable(model, i)) {
+        model.set_selected(i);
+    }
+}
+
+fn new_string_filter(property_name: &str) -> gtk::StringFilter {
+    let prop_expr = gtk::PropertyExpression::new(
+        ListItemObject::static_type(),
+        gtk::Expression::NONE,
+        property_name
+    );
+
+    gtk::StringFilter::builder()
+        .match_mode(gtk::StringFilterMatchMode::Substring)
+        .ignore_case(true)
+        .expression(prop_expr)
         .build()
 }
@@ -0,0 +1,245 @@
+/*
+ * Waymenu - A launcher/menu for wlroots based wayland compositors
+ * Copyright (C) 2024 Josh Kropf <josh@slashdev.ca>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use gtk::{gio, glib, prelude::*};
+use std::rc::Rc;
+
+use crate::env;
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_SETTINGS: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+/// Color scheme preferred by the desktop
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorScheme {
+    Default,
+    Dark,
+    Light
+}
+
+impl ColorScheme {
+    /// Suffix of the stylesheet variant for the color scheme, e.g. style-dark.css
+    pub fn variant(&self) -> Option<&'static str> {
+        match self {
+            ColorScheme::Default => None,
+            ColorScheme::Dark => Some("dark"),
+            ColorScheme::Light => Some("light")
+        }
+    }
+
+    /// Color scheme from value of the portal `color-scheme` setting
+    fn from_portal(value: &glib::Variant) -> Self {
+        // values are wrapped in one or more variants depending on the method
+        let mut value = value.clone();
+        while let Some(inner) = value.as_variant() {
+            value = inner;
+        }
+
+        match value.get::<u32>() {
+            Some(1) => ColorScheme::Dark,
+            Some(2) => ColorScheme::Light,
+            _ => ColorScheme::Default
+        }
+    }
+}
+
+/// Proxy of the desktop portal settings on the session bus. The portal isn't
+/// started when it's not already running.
+pub async fn portal_settings() -> Option<gio::DBusProxy> {
+    let connection = gio::bus_get_future(gio::BusType::Session).await
+        .map_err(|e| glib::g_debug!(env::app_name(), "Unable to connect to session bus: {e}"))
+        .ok()?;
+
+    settings_proxy(&connection, Some(PORTAL_NAME)).await
+}
+
+async fn settings_proxy(connection: &gio::DBusConnection, name: Option<&str>) -> Option<gio::DBusProxy> {
+    gio::DBusProxy::new_future(
+        connection,
+        gio::DBusProxyFlags::DO_NOT_LOAD_PROPERTIES | gio::DBusProxyFlags::DO_NOT_AUTO_START,
+        None,
+        name,
+        PORTAL_PATH,
+        PORTAL_SETTINGS
+    ).await
+        .map_err(|e| glib::g_debug!(env::app_name(), "Unable to connect to desktop portal: {e}"))
+        .ok()
+}
+
+/// Get color scheme from the desktop portal, falling back to the
+/// `gtk-application-prefer-dark-theme` setting
+pub async fn current(portal: Option<&gio::DBusProxy>) -> ColorScheme {
+    let scheme = match portal {
+        Some(portal) => portal_scheme(portal).await,
+        None => ColorScheme::Default
+    };
+
+    if scheme != ColorScheme::Default {
+        return scheme;
+    }
+
+    gtk_scheme()
+}
+
+/// Color scheme of the `gtk-application-prefer-dark-theme` setting
+pub fn gtk_scheme() -> ColorScheme {
+    match gtk::Settings::default() {
+        Some(settings) if settings.is_gtk_application_prefer_dark_theme() => ColorScheme::Dark,
+        _ => ColorScheme::Default
+    }
+}
+
+/// Read color scheme with `ReadOne`, or the deprecated `Read` of portals
+/// older than version 2 of the settings interface
+async fn portal_scheme(portal: &gio::DBusProxy) -> ColorScheme {
+    let args = (APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY).to_variant();
+
+    let reply = match portal.call_future("ReadOne", Some(&args), gio::DBusCallFlags::NONE, -1).await {
+        Ok(reply) => Ok(reply),
+        Err(e) => {
+            glib::g_debug!(env::app_name(), "Unable to read color scheme with ReadOne: {e}");
+            portal.call_future("Read", Some(&args), gio::DBusCallFlags::NONE, -1).await
+        }
+    };
+
+    match reply {
+        Ok(reply) => ColorScheme::from_portal(&reply.child_value(0)),
+        Err(e) => {
+            glib::g_debug!(env::app_name(), "Unable to read color scheme: {e}");
+            ColorScheme::Default
+        }
+    }
+}
+
+/// Call `on_change` when the portal or GTK color scheme setting changes
+pub fn connect_changed<F: Fn() + 'static>(portal: Option<&gio::DBusProxy>, on_change: F) {
+    let on_change = Rc::new(on_change);
+
+    if let Some(portal) = portal {
+        let on_change = on_change.clone();
+        portal.connect_local("g-signal", false, move |args| {
+            let signal = args.get(2).and_then(|v| v.get::<String>().ok());
+            let params = args.get(3).and_then(|v| v.get::<glib::Variant>().ok());
+
+            if let (Some("SettingChanged"), Some(params)) = (signal.as_deref(), params) {
+                let namespace = params.child_value(0).get::<String>();
+                let key = params.child_value(1).get::<String>();
+                if namespace.as_deref() == Some(APPEARANCE_NAMESPACE) && key.as_deref() == Some(COLOR_SCHEME_KEY) {
+                    on_change();
+                }
+            }
+
+            None
+        });
+    }
+
+    if let Some(settings) = gtk::Settings::default() {
+        settings.connect_gtk_application_prefer_dark_theme_notify(move |_| on_change());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    /// Peer to peer connection to a stand-in of the portal settings, with
+    /// `methods` returning the color scheme `value`. The stand-in connection
+    /// is returned along with the client connection to keep it open.
+    async fn portal_stand_in(methods: &'static [&'static str], value: u32) -> (gio::DBusConnection, gio::DBusConnection) {
+        let (server_socket, client_socket) = UnixStream::pair().unwrap();
+        // SAFETY: ownership of the sockets is transferred to the gio sockets
+        let (server_socket, client_socket) = unsafe {
+            (gio::Socket::from_fd(server_socket).unwrap(), gio::Socket::from_fd(client_socket).unwrap())
+        };
+
+        // authentication of both ends progresses together
+        let server = glib::MainContext::ref_thread_default().spawn_local(gio::DBusConnection::new_future(
+            &server_socket.connection_factory_create_connection(),
+            Some(gio::dbus_generate_guid().as_str()),
+            gio::DBusConnectionFlags::AUTHENTICATION_SERVER | gio::DBusConnectionFlags::AUTHENTICATION_ALLOW_ANONYMOUS,
+            None
+        ));
+        let client = gio::DBusConnection::new_future(
+            &client_socket.connection_factory_create_connection(),
+            None,
+            gio::DBusConnectionFlags::AUTHENTICATION_CLIENT,
+            None
+        ).await.unwrap();
+        let server = server.await.unwrap().unwrap();
+
+        let method_xml: String = methods.iter()
+            .map(|name| format!(r#"<method name="{name}">
+                <arg type="s" name="namespace" direction="in"/>
+                <arg type="s" name="key" direction="in"/>
+                <arg type="v" name="value" direction="out"/>
+            </method>"#))
+            .collect();
+        let node_xml = format!(r#"<node><interface name="{PORTAL_SETTINGS}">{method_xml}</interface></node>"#);
+        let interface = gio::DBusNodeInfo::for_xml(node_xml.as_str()).unwrap()
+            .lookup_interface(PORTAL_SETTINGS)
+            .unwrap();
+
+        server.register_object(
+            PORTAL_PATH,
+            &interface,
+            move |_, _, _, _, method, params, invocation| {
+                assert_eq!(params.get::<(String, String)>().unwrap(), (APPEARANCE_NAMESPACE.to_owned(), COLOR_SCHEME_KEY.to_owned()));
+                // Read wraps the value in a second variant
+                let value = match method {
+                    "Read" => value.to_variant().to_variant(),
+                    _ => value.to_variant()
+                };
+                invocation.return_value(Some(&(value,).to_variant()));
+            },
+            |_, _, _, _, _| unreachable!("no properties"),
+            |_, _, _, _, _, _| false
+        ).unwrap();
+
+        (server, client)
+    }
+
+    fn read_scheme(methods: &'static [&'static str], value: u32) -> ColorScheme {
+        let context = glib::MainContext::new();
+        context.with_thread_default(|| context.block_on(async {
+            let (_server, client) = portal_stand_in(methods, value).await;
+            let portal = settings_proxy(&client, None).await.unwrap();
+            portal_scheme(&portal).await
+        })).unwrap()
+    }
+
+    #[test]
+    fn read_one_color_scheme() {
+        assert_eq!(read_scheme(&["ReadOne", "Read"], 1), ColorScheme::Dark);
+        assert_eq!(read_scheme(&["ReadOne", "Read"], 2), ColorScheme::Light);
+        assert_eq!(read_scheme(&["ReadOne", "Read"], 0), ColorScheme::Default);
+    }
+
+    #[test]
+    fn read_color_scheme_without_read_one() {
+        assert_eq!(read_scheme(&["Read"], 1), ColorScheme::Dark);
+    }
+
+    #[test]
+    fn color_scheme_without_portal_methods() {
+        assert_eq!(read_scheme(&[], 1), ColorScheme::Default);
+    }
+}
@@ -16,17 +16,17 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use serde::Deserialize;
 use std::{
-    cell::{OnceCell, RefCell},
-    collections::HashMap,
+    cell::{Cell, OnceCell, RefCell},
+    collections::{HashMap, HashSet},
     fs,
+    ffi::OsStr,
     io::{self, Error, ErrorKind},
-    path::PathBuf,
-    process::Command
+    path::{Path, PathBuf},
+    time::Duration
 };
 use gtk::{gio, glib, prelude::*, subclass::prelude::*};
-use crate::env;
+use crate::{config::{CaptureTo, ExecOptions, ItemKind, ListItem, Settings, CAPTURE_TIMEOUT}, env};
 
 
 glib::wrapper! {
@@ -34,11 +34,12 @@ glib::wrapper! {
 }
 
 impl ListItemObject {
-    fn new<I: IsA<gio::Icon>>(id: &str, label: &str, executable: &str, icon: Option<&I>, launch: Launch) -> Self {
+    fn new<I: IsA<gio::Icon>>(id: &str, label: &str, executable: &str, description: &str, icon: Option<&I>, launch: Launch) -> Self {
         let obj = glib::Object::builder::<Self>()
             .property("id", id)
             .property("label", label)
             .property("executable", executable)
+            .property("description", description)
             .property("icon", &icon)
             .build();
 
@@ -49,20 +50,171 @@ impl ListItemObject {
         obj
     }
 
-    pub fn launch(&self, history_size: usize) {
+    /// Create non-selectable section header or separator
+    fn section(kind: ItemKind, label: &str) -> Self {
+        let obj = Self::new(label, label, "", "", None::<&gio::Icon>, Launch::Echo);
+        obj.set_kind(kind);
+        obj
+    }
+
+    /// Create non-selectable section header
+    pub fn header(label: &str) -> Self {
+        Self::section(ItemKind::Header, label)
+    }
+
+    /// Headers and separators can't be selected or activated
+    pub fn is_selectable(&self) -> bool {
+        self.kind() == ItemKind::Item
+    }
+
+    /// Index of the section (i.e. preceding header) the item belongs to
+    pub fn section_index(&self) -> u32 {
+        self.imp().section.get()
+    }
+
+    pub fn set_section_index(&self, section: u32) {
+        self.imp().section.set(section);
+    }
+
+    /// Launch item, with URIs passed to desktop apps
+    pub fn launch(&self, config: &Settings, uris: &[String]) -> io::Result<()> {
+        self.launch_item(config, uris)?;
+
+        if self.imp().history.get() {
+            let _ = save_history(self.id().as_str(), config.history_size)
+                .inspect_err(|e| glib::g_error!(env::app_name(), "Error {e} saving launch history"));
+        }
+
+        Ok(())
+    }
+
+    fn launch_item(&self, config: &Settings, uris: &[String]) -> io::Result<()> {
+        match self.imp().launch.get().unwrap() {
+            Launch::DesktopApp => launch_app_id(self.id().as_str(), config, uris),
+            Launch::Echo => {
+                println!("{}", self.id());
+                Ok(())
+            },
+            Launch::Exec { exec, terminal, options } => {
+                let exec = if *terminal {
+                    in_terminal(config, exec.clone())?
+                } else {
+                    exec.clone()
+                };
+
+                launch_exec(&with_launch_wrapper(config, self.id().as_str(), self.label().as_str(), exec), options, config)
+            }
+        }
+    }
+
+    /// Set desktop app as the default application for the content types
+    pub fn set_default_for_types(&self, content_types: &[String]) -> io::Result<()> {
+        if !matches!(self.imp().launch.get().unwrap(), Launch::DesktopApp) {
+            return Ok(());
+        }
+
+        let app_info = desktop_app_info(self.id().as_str())?;
+
+        for content_type in content_types.iter() {
+            app_info.set_as_default_for_type(content_type)
+                .map_err(|e| Error::other(format!("Setting default for {content_type}: {e}")))?;
+        }
+
+        Ok(())
+    }
+
+    /// Where output is sent when the item is a captured exec command
+    pub fn capture(&self) -> Option<CaptureTo> {
         match self.imp().launch.get().unwrap() {
-            Launch::DesktopApp => launch_app_id(self.id().as_str(), history_size),
-            Launch::Echo => println!("{}", self.id()),
-            Launch::Exec(exec) => launch_exec(exec).expect("Exec success")
+            Launch::Exec { options, .. } if options.capture => Some(options.capture_to),
+            _ => None
+        }
+    }
+
+    /// Run exec command and wait for its output, the command is stopped
+    /// when it doesn't exit before the timeout
+    pub async fn launch_capture(&self) -> io::Result<CaptureOutput> {
+        let Launch::Exec { exec, options, .. } = self.imp().launch.get().unwrap() else {
+            return Err(Error::new(ErrorKind::InvalidInput, "Only exec commands can be captured"));
         };
+
+        let launcher = subprocess_launcher(options, gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_PIPE);
+
+        let argv: Vec<&OsStr> = exec.iter().map(OsStr::new).collect();
+        let process = launcher.spawn(&argv)
+            .map_err(|e| Error::other(e.to_string()))?;
+
+        let timeout = Duration::from_secs(options.timeout.unwrap_or(CAPTURE_TIMEOUT));
+        let output = glib::future_with_timeout(timeout, process.communicate_utf8_future(options.stdin.clone())).await;
+
+        let (stdout, stderr, status) = match output {
+            Ok(Ok((stdout, stderr))) => {
+                let status = if process.has_exited() {
+                    CaptureStatus::Exited(process.exit_status())
+                } else {
+                    CaptureStatus::Signaled(process.term_sig())
+                };
+                (stdout, stderr, status)
+            },
+            Ok(Err(e)) => return Err(Error::other(e.to_string())),
+            Err(..) => {
+                process.force_exit();
+                (None, None, CaptureStatus::TimedOut(timeout.as_secs()))
+            }
+        };
+
+        Ok(CaptureOutput {
+            stdout: stdout.map(String::from).unwrap_or_default(),
+            stderr: stderr.map(String::from).unwrap_or_default(),
+            status
+        })
     }
 
-    pub fn app_list(history_size: usize) -> io::Result<Vec<Self>> {
-        let history = read_history(history_size)?;
+    /// List of items for each line of captured output, preceded by a header
+    /// when the command failed
+    pub fn capture_list(output: &CaptureOutput) -> Vec<Self> {
+        let mut items = Vec::new();
+
+        if let Some(error) = output.error() {
+            items.push(Self::section(ItemKind::Header, error.as_str()));
+        }
 
-        let (mut recent, mut apps): (Vec<_>, Vec<_>) = gio::AppInfo::all().iter()
-            .filter(|a| a.should_show())
+        // stderr is only of interest when the command failed
+        let stderr = if items.is_empty() { "" } else { output.stderr.as_str() };
+
+        items.extend(output.stdout.lines().chain(stderr.lines())
+            .filter(|line| !line.is_empty())
+            .map(|line| Self::new(line, line, line, "", None::<&gio::Icon>, Launch::Echo)));
+
+        items
+    }
+
+    /// Installed apps and extra entries, recent apps from launch history first.
+    /// With a `filter` only the apps it matches are listed, extra entries are
+    /// skipped since they can't e.g. open files.
+    pub fn app_list(config: &Settings, filter: Option<&dyn Fn(&gio::AppInfo) -> bool>) -> io::Result<Vec<Self>> {
+        let history = read_history(config.history_size)?;
+
+        let app_infos = gio::AppInfo::all().into_iter()
+            .chain(app_dirs_list(&config.app_dirs));
+
+        let extra_entries = match filter {
+            Some(..) => &[],
+            None => config.extra_entries.as_slice()
+        };
+        let filter = filter.unwrap_or(&|_| true);
+
+        let mut ids = HashSet::new();
+        let (mut recent, mut apps): (Vec<_>, Vec<_>) = extra_entries.iter()
+            .filter(|e| e.kind == ItemKind::Item)
             .map(Self::from)
+            .chain(app_infos
+                .filter(|a| is_visible(a, config) && filter(a))
+                .map(|a| Self::from(&a)))
+            // first item of an id wins, extra entries take the place of apps with
+            // the same id, and installed apps the place of apps in app_dirs
+            .filter(|e| ids.insert(e.id()))
+            .inspect(|e| e.imp().history.set(true))
             .partition(|e| history.contains(&e.id()));
 
         let history: HashMap<_, _> = history.iter()
@@ -76,6 +228,11 @@ impl ListItemObject {
         // sort non-recent apps alphabetically by label
         apps.sort_by(|a, b| a.label().cmp(&b.label()));
 
+        if config.history_headers && !recent.is_empty() {
+            recent.insert(0, Self::section(ItemKind::Header, "Recent"));
+            recent.push(Self::section(ItemKind::Header, "All applications"));
+        }
+
         recent.append(&mut apps);
         Ok(recent)
     }
@@ -91,15 +248,16 @@ impl ListItemObject {
 pub enum Launch {
     DesktopApp,
     Echo,
-    Exec(Vec<String>)
+    Exec { exec: Vec<String>, terminal: bool, options: ExecOptions }
 }
 
 impl From<&gio::AppInfo> for ListItemObject {
     fn from(app_info: &gio::AppInfo) -> Self {
         Self::new(
-            app_info.id().expect("AppInfo.id").as_str(),
+            app_id(app_info).expect("AppInfo.id").as_str(),
             app_info.name().as_str(),
             app_info.executable().file_name().unwrap().to_str().unwrap(),
+            app_info.description().unwrap_or_default().as_str(),
             app_info.icon().as_ref(),
             Launch::DesktopApp
         )
@@ -108,26 +266,130 @@ impl From<&gio::AppInfo> for ListItemObject {
 
 impl From<&ListItem> for ListItemObject {
     fn from(list_item: &ListItem) -> Self {
-        let icon = list_item.icon.as_ref().map(|f| {
-            let file = gio::File::for_path(f);
-            gio::FileIcon::new(&file)
-        });
+        let icon = list_item.icon.as_deref().map(icon_from_name_or_path);
 
         let launch = match &list_item.exec {
-            Some(exec) => Launch::Exec(exec.clone()),
+            Some(exec) => Launch::Exec {
+                exec: exec.clone(),
+                terminal: list_item.terminal,
+                options: list_item.exec_options.clone()
+            },
             None => Launch::Echo
         };
 
+        if list_item.kind != ItemKind::Item {
+            return Self::section(list_item.kind, list_item.label.as_str());
+        }
+
         Self::new(
             list_item.label.as_str(),
             list_item.label.as_str(),
             list_item.label.as_str(),
+            list_item.description.as_deref().unwrap_or_default(),
             icon.as_ref(),
             launch
         )
     }
 }
 
+/// Apps are listed when not hidden and shown in the current desktop, or always
+/// with `show_hidden`, and when the id matches the include/exclude patterns
+fn is_visible(app_info: &gio::AppInfo, config: &Settings) -> bool {
+    let Some(id) = app_id(app_info) else {
+        return false;
+    };
+
+    // patterns match the id with or without the .desktop suffix
+    let matches = |pattern: &String| {
+        glob_match(pattern, id.as_str()) ||
+            id.strip_suffix(".desktop").is_some_and(|id| glob_match(pattern, id))
+    };
+
+    if !config.include_apps.is_empty() && !config.include_apps.iter().any(matches) {
+        return false;
+    }
+
+    if config.exclude_apps.iter().any(matches) {
+        return false;
+    }
+
+    if config.show_hidden {
+        return true;
+    }
+
+    match (&config.desktop_names, app_info.downcast_ref::<gio::DesktopAppInfo>()) {
+        (Some(desktop_names), Some(desktop_app)) => {
+            !desktop_app.is_hidden() && !desktop_app.is_nodisplay() && shows_in(desktop_app, desktop_names)
+        },
+        // desktop is from XDG_CURRENT_DESKTOP
+        _ => app_info.should_show()
+    }
+}
+
+/// Check OnlyShowIn/NotShowIn of desktop entry against the desktop names,
+/// where the first desktop name in either list decides
+fn shows_in(desktop_app: &gio::DesktopAppInfo, desktop_names: &[String]) -> bool {
+    let only_show_in = desktop_app.string_list("OnlyShowIn");
+    let not_show_in = desktop_app.string_list("NotShowIn");
+
+    for name in desktop_names.iter() {
+        if only_show_in.iter().any(|d| d == name) {
+            return true;
+        }
+        if not_show_in.iter().any(|d| d == name) {
+            return false;
+        }
+    }
+
+    only_show_in.is_empty()
+}
+
+/// Match text against pattern, where `*` matches any characters and `?`
+/// matches a single character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // position of last `*` in pattern, and the text position it matched up to
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            },
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            },
+            _ => match star {
+                // backtrack, with the `*` matching one more character
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                },
+                None => return false
+            }
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Create icon from path to image file, or icon name from the icon theme
+fn icon_from_name_or_path(icon: &str) -> gio::Icon {
+    let path = env::expand_vars(icon);
+    let path = Path::new(path.as_str());
+    if path.is_file() {
+        gio::FileIcon::new(&gio::File::for_path(path)).upcast()
+    } else {
+        gio::ThemedIcon::new(icon).upcast()
+    }
+}
+
 mod imp {
     use super::*;
 
@@ -143,9 +405,20 @@ mod imp {
         #[property(get, set)]
         pub executable: RefCell<String>,
 
+        #[property(get, set)]
+        pub description: RefCell<String>,
+
         #[property(get, set)]
         pub icon: RefCell<Option<gio::Icon>>,
 
+        #[property(get, set, builder(ItemKind::Item))]
+        pub kind: Cell<ItemKind>,
+
+        pub section: Cell<u32>,
+
+        /// Launches are saved in the launch history
+        pub history: Cell<bool>,
+
         pub launch: OnceCell<Launch>
     }
 
@@ -159,42 +432,358 @@ mod imp {
     impl ObjectImpl for ListItemObject { }
 }
 
-#[derive(Deserialize)]
-pub struct ListItem {
-    pub label: String,
-    pub icon: Option<PathBuf>,
-    pub exec: Option<Vec<String>>
+#[derive(Debug)]
+pub enum CaptureStatus {
+    Exited(i32),
+    Signaled(i32),
+    TimedOut(u64)
+}
+
+pub struct CaptureOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: CaptureStatus
+}
+
+impl CaptureOutput {
+    /// Description of the failure when the command didn't exit successfully
+    pub fn error(&self) -> Option<String> {
+        match self.status {
+            CaptureStatus::Exited(0) => None,
+            CaptureStatus::Exited(code) => Some(format!("Command failed with exit status {code}")),
+            CaptureStatus::Signaled(signal) => Some(format!("Command killed by signal {signal}")),
+            CaptureStatus::TimedOut(secs) => Some(format!("Command timed out after {secs}s"))
+        }
+    }
 }
 
-impl ListItem {
-    fn from_json_reader<R: io::Read>(reader: R) -> io::Result<Vec<Self>> {
-        Ok(serde_json::from_reader(reader)?)
+fn launch_app_id(id: &str, config: &Settings, uris: &[String]) -> io::Result<()> {
+    let app_info = desktop_app_info(id)?;
+
+    // gio picks its own terminal for Terminal=true apps, use the terminal setting instead
+    let terminal = config.terminal.is_some() && app_info.boolean("Terminal");
+
+    if terminal || config.launch_wrapper.is_some() {
+        let options = ExecOptions {
+            cwd: app_info.string("Path").map(String::from),
+            ..ExecOptions::default()
+        };
+
+        let id = Path::new(id).file_name().and_then(|f| f.to_str()).unwrap_or(id);
+        let id = id.strip_suffix(".desktop").unwrap_or(id);
+
+        for mut exec in desktop_exec(&app_info, uris)? {
+            if terminal {
+                exec = in_terminal(config, exec)?;
+            }
+            launch_exec(&with_launch_wrapper(config, id, app_info.name().as_str(), exec), &options, config)?;
+        }
+    } else {
+        let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+        app_info.launch_uris(&uris, launch_context().as_ref())
+            .map_err(|e| Error::other(format!("Launching {id}: {e}")))?;
     }
+
+    Ok(())
 }
 
-fn launch_app_id(id: &str, history_size: usize) {
-    let app_info = gio::DesktopAppInfo::new(id)
-        .expect("DesktopAppInfo from id");
-    app_info.launch(&[], gio::AppLaunchContext::NONE)
-        .expect("Launch application");
-    let _ = save_history(id, history_size)
-        .inspect_err(|e| glib::g_error!(env::app_name(), "Error {e} saving launch history"));
+/// Desktop app of id, which is the path of the desktop file for apps from `app_dirs`
+fn desktop_app_info(id: &str) -> io::Result<gio::DesktopAppInfo> {
+    let app_info = if Path::new(id).is_absolute() {
+        gio::DesktopAppInfo::from_filename(id)
+    } else {
+        gio::DesktopAppInfo::new(id)
+    };
+
+    app_info.ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{id}: desktop entry not found")))
 }
 
-fn launch_exec(exec: &Vec<String>) -> io::Result<()> {
-    let mut exec_iter = exec.iter();
+/// Id of app, or path of the desktop file for apps outside of the XDG data dirs
+fn app_id(app_info: &gio::AppInfo) -> Option<String> {
+    app_info.id()
+        .map(String::from)
+        .or_else(|| app_info.downcast_ref::<gio::DesktopAppInfo>()
+            .and_then(|a| a.filename())
+            .map(|path| path.to_string_lossy().into_owned()))
+}
+
+/// Apps of the desktop files in directories
+fn app_dirs_list(app_dirs: &[PathBuf]) -> Vec<gio::AppInfo> {
+    let mut desktop_files: Vec<_> = app_dirs.iter()
+        .filter_map(|dir| fs::read_dir(dir)
+            .map_err(|e| glib::g_debug!(env::app_name(), "Unable to read {}: {e}", dir.to_string_lossy()))
+            .ok())
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+        .collect();
+
+    // read_dir order is platform dependent
+    desktop_files.sort();
+
+    desktop_files.iter()
+        .filter_map(gio::DesktopAppInfo::from_filename)
+        .map(|app_info| app_info.upcast())
+        .collect()
+}
+
+/// Commands of desktop app, with field codes replaced by `uris`. Apps that
+/// take a single file or URL (`%f` or `%u`) have a command for each URI.
+fn desktop_exec(app_info: &gio::DesktopAppInfo, uris: &[String]) -> io::Result<Vec<Vec<String>>> {
+    let commandline = app_info.commandline()
+        .ok_or(Error::other("Desktop entry has no Exec"))?;
+
+    let argv: Vec<String> = glib::shell_parse_argv(commandline)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?
+        .iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+
+    let single = argv.iter()
+        .flat_map(|arg| field_codes(arg))
+        .any(|code| code == 'f' || code == 'u');
+
+    let name = app_info.name();
+    let file_name = app_info.filename()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let expand = |uris: &[String]| {
+        let paths: Vec<String> = uris.iter()
+            .filter_map(|uri| gio::File::for_uri(uri).path())
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
 
-    let exec_cmd = exec_iter.next()
+        let mut exec = Vec::new();
+        for arg in argv.iter() {
+            match arg.as_str() {
+                // list field codes are an argument for each file or URL
+                "%F" => exec.extend(paths.iter().cloned()),
+                "%U" => exec.extend(uris.iter().cloned()),
+                arg => {
+                    let expanded = expand_field_codes(arg, |code| match code {
+                        'f' => paths.first().cloned().unwrap_or_default(),
+                        'u' => uris.first().cloned().unwrap_or_default(),
+                        'F' => paths.join(" "),
+                        'U' => uris.join(" "),
+                        'c' => name.to_string(),
+                        'k' => file_name.clone(),
+                        // %i and deprecated field codes are removed
+                        _ => String::new()
+                    });
+
+                    // arguments of only field codes are removed when empty
+                    if !expanded.is_empty() || field_codes(arg).is_empty() {
+                        exec.push(expanded);
+                    }
+                }
+            }
+        }
+        exec
+    };
+
+    if single && uris.len() > 1 {
+        Ok(uris.chunks(1).map(expand).collect())
+    } else {
+        Ok(vec![expand(uris)])
+    }
+}
+
+/// Field codes in desktop entry Exec argument, e.g. `f` for `%f`
+fn field_codes(arg: &str) -> Vec<char> {
+    let mut codes = Vec::new();
+    expand_field_codes(arg, |code| {
+        codes.push(code);
+        String::new()
+    });
+    codes
+}
+
+/// Replace field codes in desktop entry Exec argument, where `%%` is `%`
+fn expand_field_codes<F: FnMut(char) -> String>(arg: &str, mut value: F) -> String {
+    let mut expanded = String::new();
+    let mut chars = arg.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('%') | None => expanded.push('%'),
+            Some(code) => expanded.push_str(value(code).as_str())
+        }
+    }
+
+    expanded
+}
+
+/// Launch context of the display, which provides activation tokens for
+/// launched apps, must be used while the window still has focus
+fn launch_context() -> Option<gtk::gdk::AppLaunchContext> {
+    gtk::gdk::Display::default()
+        .map(|display| display.app_launch_context())
+}
+
+/// URI of command line argument, which is either a URI or a file path
+pub fn uri_from_arg(arg: &str) -> String {
+    gio::File::for_commandline_arg(arg).uri().into()
+}
+
+/// Command wrapped by the terminal setting
+fn in_terminal(config: &Settings, exec: Vec<String>) -> io::Result<Vec<String>> {
+    let terminal = config.terminal.as_ref()
+        .ok_or(Error::new(ErrorKind::InvalidInput, "`terminal` setting required to run command in terminal"))?;
+
+    Ok(terminal.iter().cloned().chain(exec).collect())
+}
+
+/// Command wrapped by the launch wrapper setting, when set. `{id}` and `{name}`
+/// are replaced in wrapper arguments, and an `{exec}` argument is replaced with
+/// the command, otherwise the command is appended to the wrapper. Within a
+/// larger argument, `{exec}` is replaced with the shell quoted command.
+fn with_launch_wrapper(config: &Settings, id: &str, name: &str, exec: Vec<String>) -> Vec<String> {
+    let Some(wrapper) = &config.launch_wrapper else {
+        return exec;
+    };
+
+    // menu item ids are labels, which can contain characters that aren't
+    // valid in e.g. systemd unit names
+    let id: String = id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '-' })
+        .collect();
+
+    let quoted = exec.iter()
+        .map(|arg| glib::shell_quote(arg).to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut wrapped = Vec::new();
+    let mut has_exec = false;
+
+    for arg in wrapper.iter() {
+        if arg == "{exec}" {
+            wrapped.extend(exec.iter().cloned());
+            has_exec = true;
+        } else {
+            has_exec |= arg.contains("{exec}");
+            wrapped.push(arg
+                .replace("{id}", id.as_str())
+                .replace("{name}", name)
+                .replace("{exec}", quoted.as_str()));
+        }
+    }
+
+    if !has_exec {
+        wrapped.extend(exec);
+    }
+
+    wrapped
+}
+
+/// Spawn command detached from waymenu, in a new session with stdio
+/// redirected to the launch log, so the command outlives waymenu without
+/// holding open the terminal or pipes of waymenu
+fn launch_exec(exec: &Vec<String>, options: &ExecOptions, config: &Settings) -> io::Result<()> {
+    let exec_cmd = exec.first()
         .ok_or(Error::new(ErrorKind::Other, "exec[0] required for command to execute"))?;
 
-    let mut cmd = Command::new(exec_cmd);
-    cmd.args(exec_iter);
+    // output is discarded when the launch log can't be opened, rather than
+    // failing the launch
+    let log = config.launch_log.as_ref()
+        .and_then(|log_path| open_launch_log(log_path)
+            .map_err(|e| eprintln!("Unable to open launch log {}: {e}", log_path.to_string_lossy()))
+            .ok());
+
+    let flags = match log {
+        Some(..) => gio::SubprocessFlags::NONE,
+        None => gio::SubprocessFlags::STDOUT_SILENCE | gio::SubprocessFlags::STDERR_SILENCE
+    };
+
+    let launcher = subprocess_launcher(options, flags);
 
-    cmd.spawn()?;
+    // activation token allows the compositor to focus the window of the command
+    let app_info = gio::AppInfo::create_from_commandline(exec_cmd, None, gio::AppInfoCreateFlags::SUPPORTS_STARTUP_NOTIFICATION);
+    let token = launch_context()
+        .zip(app_info.ok())
+        .and_then(|(context, app_info)| context.startup_notify_id(&app_info, &[]));
+
+    if let Some(token) = token {
+        launcher.setenv("XDG_ACTIVATION_TOKEN", token.as_str(), true);
+        launcher.setenv("DESKTOP_STARTUP_ID", token.as_str(), true);
+    }
+
+    if let Some((stdout, stderr)) = log {
+        launcher.take_stdout_fd(stdout);
+        launcher.take_stderr_fd(stderr);
+    }
+
+    // SAFETY: setsid is async-signal-safe
+    launcher.set_child_setup(|| unsafe {
+        libc::setsid();
+    });
+
+    // the subprocess reaps the child when it exits, if waymenu exits first
+    // the child is reparented to init (or the nearest subreaper) instead
+    let argv: Vec<&OsStr> = exec.iter().map(OsStr::new).collect();
+    let process = launcher.spawn(&argv)
+        .map_err(|e| Error::other(e.to_string()))?;
+
+    if let (Some(input), Some(child_stdin)) = (&options.stdin, process.stdin_pipe()) {
+        // written asynchronously so a command that is slow to read its input
+        // doesn't block the ui, the app is held until the write completes
+        let hold = gio::Application::default().map(|app| app.hold());
+        let input = input.clone().into_bytes();
+
+        glib::MainContext::default().spawn_local(async move {
+            if let Err((_, e)) = child_stdin.write_all_future(input, glib::Priority::DEFAULT).await {
+                eprintln!("Error writing to command stdin: {e}");
+            }
+            // closing stdin lets the command see end of input
+            let _ = child_stdin.close_future(glib::Priority::DEFAULT).await;
+            drop(hold);
+        });
+    }
 
     Ok(())
 }
 
+/// Launcher of exec command with the working directory, environment and
+/// stdin pipe of `options`, in addition to `flags`
+fn subprocess_launcher(options: &ExecOptions, mut flags: gio::SubprocessFlags) -> gio::SubprocessLauncher {
+    if options.stdin.is_some() {
+        flags |= gio::SubprocessFlags::STDIN_PIPE;
+    }
+
+    let launcher = gio::SubprocessLauncher::new(flags);
+
+    if let Some(cwd) = &options.cwd {
+        launcher.set_cwd(env::expand_vars(cwd));
+    }
+
+    if options.clear_env {
+        launcher.set_environ(&[]);
+    }
+
+    for (key, value) in options.env.iter() {
+        launcher.setenv(key, env::expand_vars(value), true);
+    }
+
+    launcher
+}
+
+/// Open launch log for appending, creating the log directory when needed,
+/// returns a file for each of stdout and stderr
+fn open_launch_log(log_path: &Path) -> io::Result<(fs::File, fs::File)> {
+    if let Some(dir) = log_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let log = fs::OpenOptions::new().create(true).append(true).open(log_path)?;
+    Ok((log.try_clone()?, log))
+}
+
 fn read_history(length: usize) -> io::Result<Vec<String>> {
     let history_file = env::get_history_path();
     let history = if history_file.exists() {
@@ -225,3 +814,152 @@ fn save_history(app_id: &str, length: usize) -> io::Result<()> {
     let content = history.join("\n");
     fs::write(history_file, content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_info(exec: &str) -> gio::DesktopAppInfo {
+        let key_file = glib::KeyFile::new();
+        let desktop_entry = format!("[Desktop Entry]\nType=Application\nName=Test App\nExec={exec}\n");
+        key_file.load_from_data(desktop_entry.as_str(), glib::KeyFileFlags::NONE).unwrap();
+        gio::DesktopAppInfo::from_keyfile(&key_file).unwrap()
+    }
+
+    fn uris(uris: &[&str]) -> Vec<String> {
+        uris.iter().map(|uri| uri.to_string()).collect()
+    }
+
+    #[test]
+    fn desktop_exec_list_field_codes() {
+        let uris = uris(&["file:///tmp/a", "file:///tmp/b"]);
+        assert_eq!(desktop_exec(&app_info("echo %U"), &uris).unwrap(), [["echo", "file:///tmp/a", "file:///tmp/b"]]);
+        assert_eq!(desktop_exec(&app_info("echo -- %F"), &uris).unwrap(), [["echo", "--", "/tmp/a", "/tmp/b"]]);
+    }
+
+    #[test]
+    fn desktop_exec_command_for_each_file() {
+        let uris = uris(&["file:///tmp/a", "file:///tmp/b"]);
+        assert_eq!(desktop_exec(&app_info("echo --file=%f"), &uris).unwrap(), [
+            ["echo", "--file=/tmp/a"],
+            ["echo", "--file=/tmp/b"]
+        ]);
+        assert_eq!(desktop_exec(&app_info("echo %u"), &uris).unwrap(), [
+            ["echo", "file:///tmp/a"],
+            ["echo", "file:///tmp/b"]
+        ]);
+    }
+
+    #[test]
+    fn desktop_exec_without_files() {
+        assert_eq!(desktop_exec(&app_info("echo %f --file=%f"), &[]).unwrap(), [["echo", "--file="]]);
+    }
+
+    #[test]
+    fn desktop_exec_other_field_codes() {
+        assert_eq!(desktop_exec(&app_info("echo %i %c 100%% %d"), &[]).unwrap(), [["echo", "Test App", "100%"]]);
+    }
+
+    fn with_wrapper(wrapper: &[&str]) -> Settings {
+        serde_json::from_value(serde_json::json!({ "launch_wrapper": wrapper })).unwrap()
+    }
+
+    fn exec(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn launch_wrapper_exec_arg() {
+        let config = with_wrapper(&["systemd-run", "--unit=app-{id}", "{exec}", "--after"]);
+        assert_eq!(
+            with_launch_wrapper(&config, "My App!", "My App", exec(&["a", "b c"])),
+            ["systemd-run", "--unit=app-My-App-", "a", "b c", "--after"]
+        );
+    }
+
+    #[test]
+    fn launch_wrapper_exec_within_arg() {
+        let config = with_wrapper(&["sh", "-c", "{exec} >/dev/null"]);
+        assert_eq!(
+            with_launch_wrapper(&config, "id", "name", exec(&["echo", "it's"])),
+            ["sh", "-c", r"'echo' 'it'\''s' >/dev/null"]
+        );
+    }
+
+    #[test]
+    fn launch_wrapper_appends_exec() {
+        let config = with_wrapper(&["wrap", "{name}"]);
+        assert_eq!(with_launch_wrapper(&config, "id", "My App", exec(&["a"])), ["wrap", "My App", "a"]);
+    }
+
+    #[test]
+    fn launch_without_wrapper() {
+        let config: Settings = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(with_launch_wrapper(&config, "id", "name", exec(&["a"])), ["a"]);
+    }
+
+    #[test]
+    fn app_list_filter_skips_extra_entries() {
+        let config: Settings = serde_json::from_value(serde_json::json!({
+            "history_size": 0,
+            "app_dirs": [],
+            "extra_entries": [{ "label": "Dotfiles", "exec": ["true"] }]
+        })).unwrap();
+
+        let ids = |items: Vec<ListItemObject>| items.iter().map(|i| i.id()).collect::<Vec<_>>();
+
+        assert!(ids(ListItemObject::app_list(&config, None).unwrap()).contains(&"Dotfiles".into()));
+        assert!(ListItemObject::app_list(&config, Some(&|_| false)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn launch_log_dir_is_created() {
+        let dir = std::env::temp_dir().join(format!("waymenu-test-{}", std::process::id()));
+        let log_path = dir.join("state/launch.log");
+
+        open_launch_log(&log_path).unwrap();
+        assert!(log_path.is_file());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn glob_match_star() {
+        assert!(glob_match("*", "firefox"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("org.kde.*", "org.kde.dolphin"));
+        assert!(glob_match("*kde*", "org.kde.dolphin"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYcZ"));
+        assert!(!glob_match("org.kde.*", "org.gnome.Nautilus"));
+    }
+
+    #[test]
+    fn glob_match_question_mark() {
+        assert!(glob_match("vim?", "vim9"));
+        assert!(glob_match("?im", "vim"));
+        assert!(!glob_match("vim?", "vim"));
+        assert!(!glob_match("vim?", "vim90"));
+    }
+
+    #[test]
+    fn glob_match_empty_pattern() {
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "firefox"));
+    }
+
+    #[test]
+    fn glob_match_trailing_wildcards() {
+        assert!(glob_match("firefox*", "firefox"));
+        assert!(glob_match("firefox**", "firefox"));
+        assert!(glob_match("firefox*", "firefox-esr"));
+        assert!(!glob_match("firefox*?", "firefox"));
+    }
+
+    #[test]
+    fn glob_match_is_exact_without_wildcards() {
+        assert!(glob_match("firefox", "firefox"));
+        assert!(!glob_match("firefox", "firefox-esr"));
+        assert!(!glob_match("firefox", "Firefox"));
+    }
+}
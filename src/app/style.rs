@@ -0,0 +1,154 @@
+/*
+ * Waymenu - A launcher/menu for wlroots based wayland compositors
+ * Copyright (C) 2024 Josh Kropf <josh@slashdev.ca>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use gtk::{glib, prelude::*};
+use std::{cell::Cell, fs, io, path::{Path, PathBuf}, rc::Rc};
+
+use crate::config::{ExtraCss, Theme};
+
+
+pub struct Stylesheet {
+    /// File path or name of builtin stylesheet, used in error messages
+    pub name: String,
+    pub css: String
+}
+
+impl Stylesheet {
+    pub fn from_file(file_path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            name: file_path.to_string_lossy().into_owned(),
+            css: fs::read_to_string(file_path)?
+        })
+    }
+
+    /// Stylesheets of `extra_css` setting, in order of increasing priority
+    pub fn from_extra_css(extra_css: &ExtraCss) -> Vec<io::Result<Self>> {
+        match extra_css {
+            ExtraCss::Inline(css) => vec![Ok(Self {
+                name: "<extra_css>".to_owned(),
+                css: css.clone()
+            })],
+            ExtraCss::Files(files) => files.iter()
+                .map(|file_path| Self::from_file(file_path)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", file_path.to_string_lossy()))))
+                .collect()
+        }
+    }
+
+    /// Stylesheet of theme bundled in the binary
+    pub fn theme(theme: Theme) -> Self {
+        Self {
+            name: format!("<theme {theme}>"),
+            css: theme_css(theme).to_owned()
+        }
+    }
+}
+
+/// CSS of bundled theme
+pub fn theme_css(theme: Theme) -> &'static str {
+    match theme {
+        Theme::Default => include_str!("../../assets/style.css"),
+        Theme::Compact => include_str!("../../assets/themes/compact.css"),
+        Theme::Spotlight => include_str!("../../assets/themes/spotlight.css"),
+        Theme::GridDrawer => include_str!("../../assets/themes/grid-drawer.css"),
+        Theme::HighContrast => include_str!("../../assets/themes/high-contrast.css")
+    }
+}
+
+/// Path of stylesheet variant, e.g. `style-dark.css` for `style.css`
+pub fn variant_path(file_path: &Path, variant: &str) -> PathBuf {
+    let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match file_path.extension() {
+        Some(ext) => format!("{stem}-{variant}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{variant}")
+    };
+
+    file_path.with_file_name(file_name)
+}
+
+/// Load stylesheet into provider, printing parsing errors and warnings to
+/// stderr as `file:line:col`, returns the number of errors
+pub fn load_stylesheet(provider: &gtk::CssProvider, stylesheet: &Stylesheet) -> usize {
+    let errors = Rc::new(Cell::new(0));
+
+    let name = stylesheet.name.clone();
+    let handler = provider.connect_parsing_error(glib::clone!(@strong errors => move |_, section, error| {
+        // section has a file when the error is within an @import
+        let file_name = section.file()
+            .and_then(|f| f.path())
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|| name.clone());
+
+        let location = section.start_location();
+
+        let severity = if error.kind::<gtk::CssParserWarning>().is_some() {
+            "warning"
+        } else {
+            errors.set(errors.get() + 1);
+            "error"
+        };
+
+        eprintln!("{}:{}:{}: {}: {}",
+            file_name,
+            location.lines() + 1,
+            location.line_chars() + 1,
+            severity,
+            error.message()
+        );
+    }));
+
+    provider.load_from_string(stylesheet.css.as_str());
+    provider.disconnect(handler);
+
+    errors.get()
+}
+
+/// Parse stylesheet, printing errors and warnings like `load_stylesheet`,
+/// returns the number of errors. Parsing doesn't depend on a display, so
+/// GTK isn't initialized and stylesheets can be checked headless.
+pub fn check_stylesheet(stylesheet: &Stylesheet) -> usize {
+    // gtk::CssProvider::new() asserts that GTK is initialized
+    load_stylesheet(&glib::Object::new::<gtk::CssProvider>(), stylesheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stylesheet(css: &str) -> Stylesheet {
+        Stylesheet { name: "test.css".to_owned(), css: css.to_owned() }
+    }
+
+    #[test]
+    fn check_valid_stylesheet() {
+        assert_eq!(check_stylesheet(&stylesheet("window { color: red; }")), 0);
+    }
+
+    #[test]
+    fn check_broken_stylesheet() {
+        let css = "window { color: red; }\n#list { colr: blue; }\nbox { margin: ; }";
+        assert_eq!(check_stylesheet(&stylesheet(css)), 2);
+    }
+
+    #[test]
+    fn check_bundled_themes() {
+        for theme in [Theme::Default, Theme::Compact, Theme::Spotlight, Theme::GridDrawer, Theme::HighContrast] {
+            assert_eq!(check_stylesheet(&Stylesheet::theme(theme)), 0, "{theme}");
+        }
+    }
+}
@@ -18,25 +18,30 @@
 
 pub use clap::Parser;
 use clap::{Args, Subcommand};
+use serde::Serialize;
+use serde_json::{Map, Value};
 use std::{io, path::PathBuf};
 
 use crate::env;
-use crate::config::{Orientation, Settings};
+use crate::config::{Layout, Orientation, Section, Settings, Sources, Theme};
 
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
-    /// Path to stylesheet
-    /// [default: $WAYMENU_HOME/style.css or $XDG_CONFIG_HOME/waymenu/style.css]
-    #[arg(short, long, verbatim_doc_comment)]
-    pub style: Option<PathBuf>,
-
     /// Path to config file
     /// [default: $WAYMENU_HOME/config.jsonc or $XDG_CONFIG_HOME/waymenu/config.jsonc]
     #[arg(short, long, verbatim_doc_comment)]
     pub config: Option<PathBuf>,
 
+    /// Apply settings of named profile from config file
+    #[arg(short, long)]
+    pub profile: Option<String>,
+
+    /// Treat unknown settings in config file as errors
+    #[arg(long, default_value_t = false)]
+    pub strict: bool,
+
     /// Enable verbose logging (or set env var G_MESSAGES_DEBUG=all)
     #[arg(short, default_value_t = false)]
     pub verbose: bool,
@@ -48,48 +53,81 @@ pub struct Cli {
     pub command: Commands
 }
 
-#[derive(Args)]
+/// Settings that can be set on the command line, which are serialized with
+/// the same names as `Settings` when provided
+#[derive(Args, Serialize)]
 pub struct SettingsOverride {
+    /// Path to stylesheet
+    /// [default: $WAYMENU_HOME/style.css or $XDG_CONFIG_HOME/waymenu/style.css]
+    #[arg(short, long, verbatim_doc_comment)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<PathBuf>,
+
+    /// Bundled theme loaded beneath the stylesheet
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<Theme>,
+
     #[arg(long, help = format!("Window width [default: {}]", Settings::default_width()))]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub width: Option<i32>,
 
     #[arg(long, help = format!("Window height [default: {}]", Settings::default_height()))]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub height: Option<i32>,
 
     #[arg(long, help = format!("Display menu in vertical or horizontal orientation\ndefault: {}", Settings::default_orientation()))]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub orientation: Option<Orientation>,
 
+    #[arg(long, help = format!("Display menu as a list or grid\ndefault: {}", Settings::default_layout()))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+
+    #[arg(long, help = "Fixed number of columns in grid layout")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<u32>,
+
+    #[arg(long, help = "Size of list item icons in pixels")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_size: Option<i32>,
+
     #[arg(long, help = "Hide search field")]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub hide_search: bool,
 
+    #[arg(long, help = "Hide description below item labels")]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub hide_description: bool,
+
+    #[arg(long = "all", help = "Include hidden and NoDisplay apps in launcher")]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub show_hidden: bool,
+
     #[arg(long, help = format!("Max recent apps at top of launcher list\ndefault: {} [0 disables launch history]", Settings::default_history_size()))]
-    pub history_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_size: Option<usize>
 }
 
 impl SettingsOverride {
-    fn apply(&self, settings: &mut Settings) {
-        assign_some(self.width, &mut settings.width);
-        assign_some(self.height, &mut settings.height);
-        assign_some(self.orientation, &mut settings.orientation);
-        if self.hide_search {
-            settings.hide_search = true;
-        }
-        assign_some(self.history_size, &mut settings.history_size);
-    }
-}
-
-/// If `a` is `Some`, assign value to `b`
-#[inline]
-fn assign_some<T>(a: Option<T>, b: &mut T) {
-    if let Some(val) = a {
-        *b = val;
+    /// Settings provided on the command line, by setting name
+    fn values(&self) -> Map<String, Value> {
+        let Ok(Value::Object(values)) = serde_json::to_value(self) else {
+            unreachable!("SettingsOverride serializes to an object");
+        };
+        values
     }
 }
 
 #[derive(Clone, Subcommand)]
 pub enum Commands {
     /// Show launcher for installed applications
-    Launcher,
+    Launcher {
+        /// Open files with the selected application, only applications
+        /// that handle the type of every file are listed
+        #[arg(long, num_args = 1..)]
+        files: Vec<PathBuf>
+    },
 
     /// Show custom menu of options and optionally output selection to stdout
     Menu {
@@ -98,14 +136,68 @@ pub enum Commands {
         file: Option<PathBuf>
     },
 
+    /// Show applications that handle the type of the files, and open the
+    /// files with the selected application
+    OpenWith {
+        /// Files to open
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Set the selected application as the default for the file types
+        #[arg(long)]
+        set_default: bool
+    },
+
+    /// Show text prompt and output entered text to stdout
+    Prompt {
+        /// Mask entered text, i.e. for passwords/passphrases
+        #[arg(long)]
+        password: bool,
+
+        /// Placeholder text shown when the prompt is empty
+        #[arg(long)]
+        placeholder: Option<String>
+    },
+
     /// Write default config.jsonc, style.css files and exit
-    InitConfig
+    InitConfig {
+        /// Write stylesheet of bundled theme as style.css
+        #[arg(long)]
+        theme: Option<Theme>
+    },
+
+    /// Print the effective settings, with the source of each value, and exit
+    PrintConfig {
+        /// Include settings from config file section for command
+        #[arg(long)]
+        section: Option<Section>
+    },
+
+    /// Check stylesheet for errors and exit, with non-zero status on errors
+    CheckStyle,
+
+    /// Check config file for errors and unknown settings and exit,
+    /// with non-zero status on errors
+    CheckConfig
+}
+
+impl Commands {
+    /// Config file section with settings for the command
+    pub fn section(&self) -> Option<Section> {
+        match self {
+            Commands::Launcher { .. } | Commands::OpenWith { .. } => Some(Section::Launcher),
+            Commands::Menu { .. } => Some(Section::Menu),
+            Commands::Prompt { .. } => Some(Section::Prompt),
+            Commands::PrintConfig { section } => *section,
+            _ => None
+        }
+    }
 }
 
 impl Cli {
     /// Get path to stylesheet from cli option or fallback to path in config dir
     pub fn get_style_path(&self) -> PathBuf {
-        match &self.style {
+        match &self.overrides.style {
             Some(style_path) => style_path.to_path_buf(),
             None => env::get_css_path()
         }
@@ -120,12 +212,54 @@ impl Cli {
     }
 
     pub fn load_settings(&self) -> io::Result<Settings> {
+        self.load_settings_with_sources()
+            .map(|(settings, _)| settings)
+    }
+
+    /// Load settings, along with the source of each setting that is not a default
+    pub fn load_settings_with_sources(&self) -> io::Result<(Settings, Sources)> {
         let config_path = self.get_config_path();
 
-        let mut settings = Settings::load(&config_path)?;
+        Settings::load(
+            &config_path,
+            self.command.section(),
+            self.profile.as_deref(),
+            &self.overrides.values(),
+            self.strict
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_values_of_provided_settings() {
+        let cli = Cli::parse_from(["waymenu", "--width", "10", "--all", "--theme", "grid-drawer", "launcher"]);
+        let values = cli.overrides.values();
+
+        let mut keys: Vec<_> = values.keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["show_hidden", "theme", "width"]);
+
+        // names and types match settings
+        let settings: Settings = serde_json::from_value(Value::Object(values)).unwrap();
+        assert_eq!(settings.width, 10);
+        assert!(settings.show_hidden);
+        assert!(settings.theme == Some(Theme::GridDrawer));
+    }
 
-        self.overrides.apply(&mut settings);
+    #[test]
+    fn override_values_of_every_setting() {
+        let cli = Cli::parse_from([
+            "waymenu", "--style", "a.css", "--theme", "compact", "--width", "1", "--height", "2",
+            "--orientation", "horizontal", "--layout", "grid", "--columns", "3", "--icon-size", "4",
+            "--hide-search", "--hide-description", "--all", "--history-size", "5", "launcher"
+        ]);
+        let values = cli.overrides.values();
 
-        Ok(settings)
+        assert_eq!(values.len(), 12);
+        assert!(serde_json::from_value::<Settings>(Value::Object(values)).is_ok());
     }
 }
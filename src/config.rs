@@ -20,13 +20,23 @@ use clap::ValueEnum;
 use gtk::glib;
 use json_comments::StripComments;
 use once_cell::sync::OnceCell;
-use serde::Deserialize;
-use std::{fs, io, path::Path};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, fs, io::{self, Read}, path::{Path, PathBuf}};
 
 use super::env;
 
+mod check;
+mod menu;
 
-#[derive(Copy, Clone, Deserialize)]
+pub use check::{Problem, ProblemKind};
+pub use menu::{CaptureTo, ExecOptions, ItemKind, ListItem, CAPTURE_TIMEOUT};
+
+/// Source of each setting that is not a default value
+pub type Sources = HashMap<String, Source>;
+
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Settings {
     #[serde(default = "Settings::default_width")]
     pub width: i32,
@@ -34,30 +44,202 @@ pub struct Settings {
     pub height: i32,
     #[serde(default = "Settings::default_orientation")]
     pub orientation: Orientation,
+    #[serde(default = "Settings::default_layout")]
+    pub layout: Layout,
+    #[serde(default, deserialize_with = "deserialize_optional_columns")]
+    pub columns: Option<u32>,
+    #[serde(default = "Settings::default_min_columns", deserialize_with = "deserialize_columns")]
+    pub min_columns: u32,
+    #[serde(default = "Settings::default_max_columns", deserialize_with = "deserialize_columns")]
+    pub max_columns: u32,
+    #[serde(default)]
+    pub icon_size: Option<i32>,
     #[serde(default = "Settings::default_hide_search")]
     pub hide_search: bool,
+    #[serde(default = "Settings::default_hide_description")]
+    pub hide_description: bool,
     #[serde(default = "Settings::default_history_size")]
-    pub history_size: usize
+    pub history_size: usize,
+    #[serde(default = "Settings::default_history_headers")]
+    pub history_headers: bool,
+    #[serde(default = "Settings::default_show_hidden")]
+    pub show_hidden: bool,
+    #[serde(default)]
+    pub desktop_names: Option<Vec<String>>,
+    #[serde(default = "Settings::default_include_apps")]
+    pub include_apps: Vec<String>,
+    #[serde(default = "Settings::default_exclude_apps")]
+    pub exclude_apps: Vec<String>,
+    #[serde(default = "Settings::default_app_dirs")]
+    pub app_dirs: Vec<PathBuf>,
+    #[serde(default = "Settings::default_extra_entries")]
+    pub extra_entries: Vec<ListItem>,
+    #[serde(default)]
+    pub terminal: Option<Vec<String>>,
+    #[serde(default)]
+    pub launch_wrapper: Option<Vec<String>>,
+    #[serde(default)]
+    pub launch_log: Option<PathBuf>,
+    #[serde(default)]
+    pub theme: Option<Theme>,
+    #[serde(default)]
+    pub style: Option<PathBuf>,
+    #[serde(default)]
+    pub extra_css: Option<ExtraCss>
 }
 
 impl Settings {
-    pub fn load(file_path: &Path) -> io::Result<Self> {
-        match fs::read_to_string(file_path) {
-            Ok(json) => Self::load_json(json.as_str()),
-            Err(..) => {
-                glib::g_debug!(env::app_name(), "Unable to load {}, using default settings", file_path.to_string_lossy());
-                Ok(Self::defaults().clone())
+    /// Load settings from file, or defaults when file doesn't exist, with
+    /// settings of command `section`, `profile` then command line `overrides`
+    /// applied over the top level settings. In `strict` mode, unknown settings
+    /// are treated as errors.
+    pub fn load(file_path: &Path, section: Option<Section>, profile: Option<&str>, overrides: &Map<String, Value>, strict: bool) -> io::Result<(Self, Sources)> {
+        let layers = if file_path.exists() {
+            let mut layers = Vec::new();
+            load_layers(file_path, Source::File, strict, &mut Vec::new(), &mut layers)?;
+            layers
+        } else {
+            glib::g_debug!(env::app_name(), "Unable to load {}, using default settings", file_path.to_string_lossy());
+            Vec::new()
+        };
+
+        let mut merged = Map::new();
+        let mut sources = Sources::new();
+
+        for layer in layers.iter() {
+            layer.merge(&mut merged, &mut sources, &layer.root, layer.source.clone());
+        }
+
+        if let Some(section) = section {
+            for layer in layers.iter() {
+                let section_settings = layer.root.get(section.key()).and_then(Value::as_object);
+                if let Some(section_settings) = section_settings {
+                    layer.merge(&mut merged, &mut sources, section_settings, Source::Section(section));
+                }
+            }
+        }
+
+        if let Some(name) = profile {
+            let mut found = false;
+            for layer in layers.iter() {
+                let profile_settings = layer.root.get("profiles")
+                    .and_then(|profiles| profiles.get(name))
+                    .and_then(Value::as_object);
+                if let Some(profile_settings) = profile_settings {
+                    layer.merge(&mut merged, &mut sources, profile_settings, Source::Profile(name.to_owned()));
+                    found = true;
+                }
+            }
+
+            if !found {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}: profile `{name}` not found", file_path.to_string_lossy())
+                ));
+            }
+        }
+
+        for (key, value) in overrides.iter() {
+            merged.insert(key.clone(), value.clone());
+            sources.insert(key.clone(), Source::Cli);
+        }
+
+        let settings: Self = serde_json::from_value(Value::Object(merged))?;
+
+        if settings.min_columns > settings.max_columns {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("min_columns ({}) is greater than max_columns ({})", settings.min_columns, settings.max_columns)
+            ));
+        }
+
+        Ok((settings, sources))
+    }
+
+    /// Check config file and the files it includes for syntax errors,
+    /// unknown settings and invalid values
+    pub fn check(file_path: &Path) -> io::Result<Vec<(PathBuf, Problem)>> {
+        let json = fs::read_to_string(file_path)?;
+        let stripped = strip_comments(json.as_str())?;
+
+        let mut problems: Vec<_> = check::check_json::<Self, _>(stripped.as_str(), is_section, RESERVED_KEYS)
+            .into_iter()
+            .map(|p| (file_path.to_owned(), p))
+            .collect();
+
+        // includes are checked when the file itself parses
+        if let Ok(root) = serde_json::from_str::<Map<String, Value>>(stripped.as_str()) {
+            for include in includes(file_path, &root)? {
+                let include_problems = Self::check(&include)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", include.to_string_lossy())))?;
+                problems.extend(include_problems);
             }
         }
+
+        Ok(problems)
+    }
+
+    /// Config file followed by the files it includes, skipping included
+    /// files that can't be parsed
+    pub fn files(file_path: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        collect_files(file_path, &mut files);
+        files
+    }
+
+    /// Path to stylesheet from settings, or fallback to path in config dir
+    pub fn style_path(&self) -> PathBuf {
+        self.style.clone()
+            .unwrap_or_else(env::get_css_path)
+    }
+
+    /// Format settings as JSONC, with a comment after each setting
+    /// naming the source of the value
+    pub fn format_jsonc(&self, sources: &Sources) -> String {
+        let Ok(Value::Object(object)) = serde_json::to_value(self) else {
+            unreachable!("Settings serialize to an object");
+        };
+
+        // match order of fields in struct rather than order of map keys
+        let values: Vec<_> = check::struct_fields::<Self>().iter()
+            .filter_map(|key| object.get(*key).map(|value| (*key, value)))
+            .collect();
+
+        let jsonc = values.iter()
+            .enumerate()
+            .map(|(i, (key, value))| {
+                let comma = if i + 1 < values.len() { "," } else { "" };
+                let source = sources.get(*key).unwrap_or(&Source::Default);
+                format!("    \"{key}\": {value}{comma} // {source}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!("{{\n{jsonc}\n}}")
     }
 
-    fn load_json(json: &str) -> io::Result<Self> {
-        let stripped = StripComments::new(json.as_bytes());
-        Ok(serde_json::from_reader(stripped)?)
+    /// Parse config file json, returning problems that are errors
+    fn parse_json(json: &str, strict: bool) -> Result<Map<String, Value>, Vec<Problem>> {
+        let stripped = strip_comments(json)
+            .map_err(|e| vec![Problem { kind: ProblemKind::Syntax, line: 1, column: 1, message: e.to_string() }])?;
+
+        let problems: Vec<_> = check::check_json::<Self, _>(stripped.as_str(), is_section, RESERVED_KEYS)
+            .into_iter()
+            .filter(|p| strict || p.kind != ProblemKind::UnknownSetting)
+            .collect();
+
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        serde_json::from_str(stripped.as_str())
+            .map_err(|e| vec![e.into()])
     }
 
     fn load_defaults() -> io::Result<Self> {
-        Self::load_json(include_str!("../assets/config.jsonc"))
+        // not checked, since checking values depends on the defaults
+        let json = strip_comments(include_str!("../assets/config.jsonc"))?;
+        Ok(serde_json::from_str(json.as_str())?)
     }
 
     fn defaults() -> &'static Self {
@@ -68,18 +250,274 @@ impl Settings {
         })
     }
 
+    /// Get min/max grid columns, where `columns` takes precedence when set
+    pub fn grid_columns(&self) -> (u32, u32) {
+        match self.columns {
+            Some(columns) => (columns, columns),
+            None => (self.min_columns, self.max_columns)
+        }
+    }
+
     pub fn default_width() -> i32 { Self::defaults().width }
     pub fn default_height() -> i32 { Self::defaults().height }
     pub fn default_orientation() -> Orientation { Self::defaults().orientation }
+    pub fn default_layout() -> Layout { Self::defaults().layout }
+    pub fn default_min_columns() -> u32 { Self::defaults().min_columns }
+    pub fn default_max_columns() -> u32 { Self::defaults().max_columns }
     pub fn default_hide_search() -> bool { Self::defaults().hide_search }
+    pub fn default_hide_description() -> bool { Self::defaults().hide_description }
     pub fn default_history_size() -> usize { Self::defaults().history_size }
+    pub fn default_history_headers() -> bool { Self::defaults().history_headers }
+    pub fn default_show_hidden() -> bool { Self::defaults().show_hidden }
+    pub fn default_include_apps() -> Vec<String> { Self::defaults().include_apps.clone() }
+    pub fn default_exclude_apps() -> Vec<String> { Self::defaults().exclude_apps.clone() }
+    pub fn default_app_dirs() -> Vec<PathBuf> { Self::defaults().app_dirs.clone() }
+    pub fn default_extra_entries() -> Vec<ListItem> { Self::defaults().extra_entries.clone() }
+}
+
+/// CSS loaded over the stylesheet, each file at a higher priority than the last
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ExtraCss {
+    Inline(String),
+    Files(Vec<PathBuf>)
+}
+
+/// Where the value of a setting came from
+#[derive(Clone)]
+pub enum Source {
+    Default,
+    File,
+    Include(PathBuf),
+    Section(Section),
+    Profile(String),
+    Cli
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => f.write_str("default"),
+            Source::File => f.write_str("file"),
+            Source::Include(path) => write!(f, "include {}", path.to_string_lossy()),
+            Source::Section(section) => write!(f, "{section} section"),
+            Source::Profile(name) => write!(f, "profile {name}"),
+            Source::Cli => f.write_str("cli")
+        }
+    }
+}
+
+/// Section of config file with settings for a command
+#[derive(Copy, Clone, ValueEnum)]
+pub enum Section {
+    Launcher,
+    Menu,
+    Prompt
+}
+
+impl Section {
+    const ALL: [Section; 3] = [Section::Launcher, Section::Menu, Section::Prompt];
+
+    fn key(&self) -> &'static str {
+        match self {
+            Section::Launcher => "launcher",
+            Section::Menu => "menu",
+            Section::Prompt => "prompt"
+        }
+    }
+}
+
+impl std::fmt::Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.key())
+    }
+}
+
+/// Objects in config file that contain settings, other than the root object
+fn is_section(path: &[String]) -> bool {
+    match path {
+        // "profiles": { "name": { settings... } }
+        [profiles] | [profiles, _] if profiles == "profiles" => true,
+        // "launcher": { settings... }
+        [key] => Section::ALL.iter().any(|s| s.key() == key),
+        _ => false
+    }
+}
+
+/// Keys of config file root object that are neither settings nor sections
+const RESERVED_KEYS: &[&str] = &["include"];
+
+/// Settings with path values (or lists of paths), relative paths are
+/// resolved against the directory of the config file that sets them
+const PATH_SETTINGS: &[&str] = &["app_dirs", "launch_log", "style", "extra_css"];
+
+/// Number of grid columns, which must be at least one
+fn deserialize_columns<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    match u32::deserialize(deserializer)? {
+        0 => Err(de::Error::invalid_value(de::Unexpected::Unsigned(0), &"at least 1 column")),
+        columns => Ok(columns)
+    }
+}
+
+fn deserialize_optional_columns<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    match Option::<u32>::deserialize(deserializer)? {
+        Some(0) => Err(de::Error::invalid_value(de::Unexpected::Unsigned(0), &"at least 1 column")),
+        columns => Ok(columns)
+    }
+}
+
+/// Settings with command values, where env vars are expanded in each argument
+const COMMAND_SETTINGS: &[&str] = &["terminal", "launch_wrapper"];
+
+/// Root object of config file, or of a file it includes
+struct Layer {
+    root: Map<String, Value>,
+    source: Source,
+    dir: PathBuf
+}
+
+impl Layer {
+    /// Copy settings from object into merged settings, excluding sections
+    fn merge(&self, merged: &mut Map<String, Value>, sources: &mut Sources, object: &Map<String, Value>, source: Source) {
+        for (key, value) in object.iter() {
+            if is_section(std::slice::from_ref(key)) || RESERVED_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+
+            let mut value = value.clone();
+            if PATH_SETTINGS.contains(&key.as_str()) {
+                self.resolve_paths(key, &mut value);
+            } else if COMMAND_SETTINGS.contains(&key.as_str()) {
+                expand_command(&mut value);
+            }
+
+            merged.insert(key.clone(), value);
+            sources.insert(key.clone(), source.clone());
+        }
+    }
+
+    /// Expand env vars in path values, and resolve relative paths against
+    /// directory of the config file
+    fn resolve_paths(&self, key: &str, value: &mut Value) {
+        let resolve = |path: &mut String| *path = self.dir.join(env::expand_vars(path)).to_string_lossy().into_owned();
+
+        match value {
+            // inline css is the only string value that isn't a path
+            Value::String(path) if key != "extra_css" => resolve(path),
+            Value::Array(paths) => paths.iter_mut()
+                .filter_map(|path| match path {
+                    Value::String(path) => Some(path),
+                    _ => None
+                })
+                .for_each(resolve),
+            _ => {}
+        }
+    }
+}
+
+/// Expand env vars in arguments of a command value
+fn expand_command(value: &mut Value) {
+    if let Value::Array(args) = value {
+        for arg in args.iter_mut() {
+            if let Value::String(arg) = arg {
+                *arg = env::expand_vars(arg);
+            }
+        }
+    }
+}
+
+/// Load file and the files it includes into `layers`, in order of precedence
+/// where included files come before the including file
+fn load_layers(file_path: &Path, source: Source, strict: bool, visited: &mut Vec<PathBuf>, layers: &mut Vec<Layer>) -> io::Result<()> {
+    let canonical = file_path.canonicalize()
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", file_path.to_string_lossy())))?;
+
+    if visited.contains(&canonical) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: include cycle", file_path.to_string_lossy())
+        ));
+    }
+    visited.push(canonical);
+
+    let json = fs::read_to_string(file_path)?;
+    let root = Settings::parse_json(json.as_str(), strict)
+        .map_err(|problems| invalid_data(file_path, &problems))?;
+
+    for include in includes(file_path, &root)? {
+        load_layers(&include, Source::Include(include.clone()), strict, visited, layers)?;
+    }
+
+    visited.pop();
+
+    layers.push(Layer {
+        root,
+        source,
+        dir: file_path.parent().map(Path::to_owned).unwrap_or_default()
+    });
+
+    Ok(())
+}
+
+/// Add file and the files it includes to `files`, skipping files already added
+fn collect_files(file_path: &Path, files: &mut Vec<PathBuf>) {
+    if files.iter().any(|f| f == file_path) {
+        return;
+    }
+    files.push(file_path.to_owned());
+
+    let root = fs::read_to_string(file_path).ok()
+        .and_then(|json| strip_comments(json.as_str()).ok())
+        .and_then(|json| serde_json::from_str::<Map<String, Value>>(json.as_str()).ok());
+
+    if let Some(root) = root {
+        for include in includes(file_path, &root).unwrap_or_default() {
+            collect_files(&include, files);
+        }
+    }
+}
+
+/// Paths of files included by config file, relative to the config file
+fn includes(file_path: &Path, root: &Map<String, Value>) -> io::Result<Vec<PathBuf>> {
+    let dir = file_path.parent().unwrap_or(Path::new(""));
+    let invalid = || io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: expected a list of paths for `include`", file_path.to_string_lossy())
+    );
+
+    match root.get("include") {
+        None => Ok(Vec::new()),
+        Some(Value::Array(paths)) => paths.iter()
+            .map(|path| path.as_str()
+                .map(|path| dir.join(env::expand_vars(path)))
+                .ok_or_else(invalid))
+            .collect(),
+        Some(..) => Err(invalid())
+    }
+}
+
+fn strip_comments(json: &str) -> io::Result<String> {
+    // comments are replaced with whitespace, so line/column of errors are retained
+    let mut stripped = String::new();
+    StripComments::new(json.as_bytes()).read_to_string(&mut stripped)?;
+    Ok(stripped)
 }
 
-#[derive(Copy, Clone, Deserialize, ValueEnum)]
+/// Error listing each problem prefixed by the config file path
+fn invalid_data(file_path: &Path, problems: &[Problem]) -> io::Error {
+    let message = problems.iter()
+        .map(|p| format!("{}:{p}", file_path.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize, ValueEnum)]
 pub enum Orientation {
-    #[serde(alias = "horizontal")]
+    #[serde(rename = "horizontal", alias = "Horizontal")]
     Horizontal,
-    #[serde(alias = "vertical")]
+    #[serde(rename = "vertical", alias = "Vertical")]
     Vertical
 }
 
@@ -100,3 +538,122 @@ impl std::fmt::Display for Orientation {
         }
     }
 }
+
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize, ValueEnum)]
+pub enum Layout {
+    #[serde(rename = "list", alias = "List")]
+    List,
+    #[serde(rename = "grid", alias = "Grid")]
+    Grid
+}
+
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Layout::List => f.write_str("list"),
+            Layout::Grid => f.write_str("grid")
+        }
+    }
+}
+
+/// Stylesheets bundled with waymenu
+#[derive(Copy, Clone, Default, PartialEq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Default,
+    Compact,
+    Spotlight,
+    GridDrawer,
+    HighContrast
+}
+
+impl std::fmt::Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Theme::Default => f.write_str("default"),
+            Theme::Compact => f.write_str("compact"),
+            Theme::Spotlight => f.write_str("spotlight"),
+            Theme::GridDrawer => f.write_str("grid-drawer"),
+            Theme::HighContrast => f.write_str("high-contrast")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(json: &str, strict: bool) -> Vec<(ProblemKind, usize, usize)> {
+        Settings::parse_json(json, strict).err().unwrap_or_default().into_iter()
+            .map(|p| (p.kind, p.line, p.column))
+            .collect()
+    }
+
+    const JSON: &str = r#"{
+  // "width": 1
+  /* "a": { */ "widht": 1, // "x"
+  "height": "tall",
+  "profiles": {
+    "p": { "extra_entries": [{ "label": "x", "icn": "y" }] }
+  }
+}"#;
+
+    #[test]
+    fn problem_positions_after_comments() {
+        assert_eq!(problems(JSON, true), [
+            (ProblemKind::UnknownSetting, 3, 16),
+            (ProblemKind::InvalidValue, 4, 3)
+        ]);
+    }
+
+    #[test]
+    fn unknown_settings_only_errors_when_strict() {
+        assert_eq!(problems(JSON, false), [(ProblemKind::InvalidValue, 4, 3)]);
+    }
+
+    fn load(overrides: Value) -> io::Result<Settings> {
+        let Value::Object(overrides) = overrides else { unreachable!() };
+        Settings::load(Path::new("/nonexistent/config.jsonc"), None, None, &overrides, false)
+            .map(|(settings, _)| settings)
+    }
+
+    #[test]
+    fn columns_at_least_one() {
+        assert_eq!(load(serde_json::json!({ "columns": 2 })).unwrap().grid_columns(), (2, 2));
+        assert!(load(serde_json::json!({ "columns": 0 })).is_err());
+        assert!(load(serde_json::json!({ "min_columns": 0 })).is_err());
+        assert!(load(serde_json::json!({ "max_columns": 0 })).is_err());
+        assert_eq!(problems(r#"{ "columns": 0 }"#, false), [(ProblemKind::InvalidValue, 1, 3)]);
+    }
+
+    fn merge(root: Value) -> Map<String, Value> {
+        let Value::Object(root) = root else { unreachable!() };
+        let layer = Layer { root, source: Source::File, dir: PathBuf::from("/etc/waymenu") };
+
+        let mut merged = Map::new();
+        layer.merge(&mut merged, &mut Sources::new(), &layer.root, Source::File);
+        merged
+    }
+
+    #[test]
+    fn terminal_args_are_expanded() {
+        std::env::set_var("WAYMENU_TEST_TERM", "/opt/term");
+        let merged = merge(serde_json::json!({ "terminal": ["$WAYMENU_TEST_TERM/bin/foot", "-e", "$$1"] }));
+        assert_eq!(merged["terminal"], serde_json::json!(["/opt/term/bin/foot", "-e", "$1"]));
+    }
+
+    #[test]
+    fn launch_wrapper_args_are_expanded() {
+        let home = std::env::var("HOME").unwrap();
+        let merged = merge(serde_json::json!({ "launch_wrapper": ["~/bin/wrap", "--unit=app-{id}"] }));
+        assert_eq!(merged["launch_wrapper"], serde_json::json!([format!("{home}/bin/wrap"), "--unit=app-{id}"]));
+    }
+
+    #[test]
+    fn min_columns_not_greater_than_max() {
+        assert_eq!(load(serde_json::json!({ "min_columns": 3, "max_columns": 3 })).unwrap().grid_columns(), (3, 3));
+        let error = load(serde_json::json!({ "min_columns": 4, "max_columns": 3 })).err().unwrap();
+        assert_eq!(error.to_string(), "min_columns (4) is greater than max_columns (3)");
+    }
+}
@@ -0,0 +1,350 @@
+/*
+ * Waymenu - A launcher/menu for wlroots based wayland compositors
+ * Copyright (C) 2024 Josh Kropf <josh@slashdev.ca>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use serde::{de::{self, Visitor}, forward_to_deserialize_any, Deserialize, Deserializer};
+use serde_json::{Map, Value};
+use std::fmt;
+
+
+#[derive(Debug, PartialEq)]
+pub enum ProblemKind {
+    Syntax,
+    UnknownSetting,
+    InvalidValue
+}
+
+/// Problem found in a config file at 1-based line and column
+#[derive(Debug)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl From<serde_json::Error> for Problem {
+    fn from(e: serde_json::Error) -> Self {
+        // remove " at line X column Y" suffix since position is in problem
+        let message = e.to_string();
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_owned(),
+            None => message
+        };
+
+        Self { kind: ProblemKind::Syntax, line: e.line(), column: e.column(), message }
+    }
+}
+
+/// Check json (with comments stripped) for syntax errors, unknown keys and
+/// invalid values of the fields of `T`. Objects at paths where `is_section`
+/// is true can contain fields of `T` and nested sections. `reserved` keys of
+/// the root object are not checked.
+pub fn check_json<T, F>(json: &str, is_section: F, reserved: &[&str]) -> Vec<Problem>
+where
+    T: for<'de> Deserialize<'de>,
+    F: Fn(&[String]) -> bool
+{
+    let root = match serde_json::from_str::<Value>(json) {
+        Ok(root @ Value::Object(..)) => root,
+        Ok(..) => return vec![Problem {
+            kind: ProblemKind::Syntax,
+            line: 1,
+            column: 1,
+            message: "expected an object of settings".to_owned()
+        }],
+        Err(e) => return vec![e.into()]
+    };
+
+    let fields = struct_fields::<T>();
+
+    key_offsets(json).into_iter()
+        .filter_map(|(path, offset)| {
+            let (parent, key) = path.split_at(path.len() - 1);
+            let key = &key[0];
+
+            // only check keys of the root object and sections
+            if !parent.is_empty() && !is_section(parent) {
+                return None;
+            }
+
+            if parent.is_empty() && reserved.contains(&key.as_str()) {
+                return None;
+            }
+
+            let value = path.iter().try_fold(&root, |v, k| v.get(k))?;
+            let (line, column) = line_column(json, offset);
+
+            let (kind, message) = if is_section(&path) {
+                if value.is_object() {
+                    return None;
+                }
+                (ProblemKind::InvalidValue, format!("expected an object for `{key}`"))
+            } else if !fields.contains(&key.as_str()) {
+                let message = match suggest(key, fields) {
+                    Some(field) => format!("unknown setting `{key}`, did you mean `{field}`?"),
+                    None => format!("unknown setting `{key}`")
+                };
+                (ProblemKind::UnknownSetting, message)
+            } else {
+                // deserialize the single value so each invalid value is reported
+                let single = Map::from_iter([(key.clone(), value.clone())]);
+                let e = serde_json::from_value::<T>(Value::Object(single)).err()?;
+                (ProblemKind::InvalidValue, format!("invalid value for `{key}`: {e}"))
+            };
+
+            Some(Problem { kind, line, column, message })
+        })
+        .collect()
+}
+
+/// Find the most similar field name to an unknown key
+fn suggest<'a>(key: &str, fields: &[&'a str]) -> Option<&'a str> {
+    fields.iter()
+        .map(|f| (strsim::jaro(key, f), *f))
+        .filter(|(confidence, _)| *confidence > 0.8)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, f)| f)
+}
+
+/// Byte offsets of the keys of all objects in json, along with the path
+/// of keys to each key (array elements are in the path as "[]")
+fn key_offsets(json: &str) -> Vec<(Vec<String>, usize)> {
+    enum Frame {
+        Object { key: String, expect_key: bool },
+        Array
+    }
+
+    let mut keys = vec![];
+    let mut stack = vec![];
+
+    let mut chars = json.char_indices();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '{' => stack.push(Frame::Object { key: String::new(), expect_key: true }),
+            '[' => stack.push(Frame::Array),
+            '}' | ']' => { stack.pop(); },
+            ',' => if let Some(Frame::Object { expect_key, .. }) = stack.last_mut() {
+                *expect_key = true;
+            },
+            '"' => {
+                let mut end = json.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => { chars.next(); },
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        },
+                        _ => {}
+                    }
+                }
+
+                if let Some(Frame::Object { key, expect_key: expect_key @ true }) = stack.last_mut() {
+                    // unescaped, so the key matches the key of the parsed object
+                    *key = serde_json::from_str(&json[offset..end]).unwrap_or_default();
+                    *expect_key = false;
+
+                    let path = stack.iter()
+                        .map(|f| match f {
+                            Frame::Object { key, .. } => key.clone(),
+                            Frame::Array => "[]".to_owned()
+                        })
+                        .collect();
+
+                    keys.push((path, offset));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    keys
+}
+
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = match before.rfind('\n') {
+        Some(i) => before[i+1..].chars().count() + 1,
+        None => before.chars().count() + 1
+    };
+    (line, column)
+}
+
+/// Get names of the fields of a struct that derives `Deserialize`
+pub fn struct_fields<T: for<'de> Deserialize<'de>>() -> &'static [&'static str] {
+    // derived implementations pass the field names to `deserialize_struct`
+    struct FieldsDeserializer<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de, 'a> Deserializer<'de> for FieldsDeserializer<'a> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields captured"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsDeserializer(&mut fields));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Test {
+        #[serde(default)]
+        width: i32,
+        #[serde(default)]
+        hide_search: bool,
+        #[serde(default)]
+        extra_entries: Vec<Value>
+    }
+
+    fn is_section(path: &[String]) -> bool {
+        match path {
+            [profiles] | [profiles, _] if profiles == "profiles" => true,
+            [key] => key == "launcher",
+            _ => false
+        }
+    }
+
+    fn check(json: &str) -> Vec<(ProblemKind, usize, usize)> {
+        check_json::<Test, _>(json, is_section, &["include"]).into_iter()
+            .map(|p| (p.kind, p.line, p.column))
+            .collect()
+    }
+
+    fn paths(json: &str) -> Vec<String> {
+        key_offsets(json).into_iter()
+            .map(|(path, _)| path.join("."))
+            .collect()
+    }
+
+    #[test]
+    fn key_offsets_of_nested_objects() {
+        let json = r#"{ "a": 1, "b": { "c": [ { "d": 2 } ], "e": "f" } }"#;
+        assert_eq!(paths(json), ["a", "b", "b.c", "b.c.[].d", "b.e"]);
+        assert_eq!(key_offsets(json)[3].1, json.find(r#""d""#).unwrap());
+    }
+
+    #[test]
+    fn key_offsets_skip_string_values() {
+        let json = r#"{ "a": "{ \"x\": 1 }", "b": [ "c", ":" ], "d": "," }"#;
+        assert_eq!(paths(json), ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn key_offsets_with_escaped_quotes() {
+        let json = r#"{ "a\"b": 1, "c\u0064": 2 }"#;
+        assert_eq!(paths(json), ["a\"b", "cd"]);
+        assert_eq!(key_offsets(json)[1].1, json.find(r#""c\u"#).unwrap());
+    }
+
+    #[test]
+    fn line_column_is_one_based() {
+        let text = "{\n  \"é\": 1,\n  \"x\": 2\n}";
+        assert_eq!(line_column(text, 0), (1, 1));
+        assert_eq!(line_column(text, text.find("\"x\"").unwrap()), (3, 3));
+        // columns count characters rather than bytes
+        assert_eq!(line_column("\"é\", \"x\"", "\"é\", ".len()), (1, 6));
+    }
+
+    #[test]
+    fn check_valid_settings() {
+        let json = r#"{ "include": ["a.jsonc"], "width": 10, "launcher": { "hide_search": true } }"#;
+        assert!(check(json).is_empty());
+    }
+
+    #[test]
+    fn check_unknown_setting_with_suggestion() {
+        let problems = check_json::<Test, _>("{\n  \"widht\": 10\n}", is_section, &[]);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::UnknownSetting);
+        assert_eq!((problems[0].line, problems[0].column), (2, 3));
+        assert_eq!(problems[0].message, "unknown setting `widht`, did you mean `width`?");
+    }
+
+    #[test]
+    fn check_invalid_value() {
+        let problems = check("{ \"width\": \"wide\" }");
+        assert_eq!(problems, [(ProblemKind::InvalidValue, 1, 3)]);
+    }
+
+    #[test]
+    fn check_sections_and_profiles() {
+        let json = "{\n  \"launcher\": { \"widht\": 1 },\n  \"profiles\": {\n    \"p\": { \"hide_serch\": true, \"width\": \"x\" }\n  },\n  \"menu\": {}\n}";
+        assert_eq!(check(json), [
+            (ProblemKind::UnknownSetting, 2, 17),
+            (ProblemKind::UnknownSetting, 4, 12),
+            (ProblemKind::InvalidValue, 4, 32),
+            (ProblemKind::UnknownSetting, 6, 3)
+        ]);
+    }
+
+    #[test]
+    fn check_section_not_an_object() {
+        assert_eq!(check("{ \"launcher\": 1 }"), [(ProblemKind::InvalidValue, 1, 3)]);
+    }
+
+    #[test]
+    fn check_ignores_keys_of_setting_values() {
+        // keys of objects within extra_entries aren't settings
+        let json = r#"{ "extra_entries": [ { "label": "a", "exec": ["b"] } ] }"#;
+        assert!(check(json).is_empty());
+    }
+
+    #[test]
+    fn check_syntax_error() {
+        let problems = check_json::<Test, _>("{\n  \"width\": 1,\n}", is_section, &[]);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::Syntax);
+        assert_eq!(problems[0].line, 3);
+        assert!(!problems[0].message.contains(" at line "));
+    }
+
+    #[test]
+    fn fields_of_struct() {
+        assert_eq!(struct_fields::<Test>(), ["width", "hide_search", "extra_entries"]);
+    }
+}
@@ -0,0 +1,157 @@
+/*
+ * Waymenu - A launcher/menu for wlroots based wayland compositors
+ * Copyright (C) 2024 Josh Kropf <josh@slashdev.ca>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io};
+use gtk::glib;
+
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, glib::Enum)]
+#[enum_type(name = "ListItemKind")]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    #[default]
+    Item,
+    Header,
+    Separator
+}
+
+impl ItemKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ItemKind::Item => "item",
+            ItemKind::Header => "header",
+            ItemKind::Separator => "separator"
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(try_from = "ListItemFields")]
+pub struct ListItem {
+    #[serde(rename = "type")]
+    pub kind: ItemKind,
+    pub label: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub exec: Option<Vec<String>>,
+    pub terminal: bool,
+    #[serde(flatten)]
+    pub exec_options: ExecOptions
+}
+
+/// Fields of a list item as written in json, where `label` is optional only
+/// for separators
+#[derive(Deserialize)]
+struct ListItemFields {
+    #[serde(default, rename = "type")]
+    kind: ItemKind,
+    label: Option<String>,
+    description: Option<String>,
+    icon: Option<String>,
+    exec: Option<Vec<String>>,
+    #[serde(default)]
+    terminal: bool,
+    #[serde(flatten)]
+    exec_options: ExecOptions
+}
+
+impl TryFrom<ListItemFields> for ListItem {
+    type Error = String;
+
+    fn try_from(fields: ListItemFields) -> Result<Self, Self::Error> {
+        let label = match (fields.kind, fields.label) {
+            (ItemKind::Separator, label) => label.unwrap_or_default(),
+            (_, Some(label)) if !label.is_empty() => label,
+            (kind, _) => return Err(format!("`label` required for {} entries", kind.name()))
+        };
+
+        Ok(Self {
+            kind: fields.kind,
+            label,
+            description: fields.description,
+            icon: fields.icon,
+            exec: fields.exec,
+            terminal: fields.terminal,
+            exec_options: fields.exec_options
+        })
+    }
+}
+
+/// Seconds to wait for a captured command before it's stopped
+pub const CAPTURE_TIMEOUT: u64 = 10;
+
+/// Where output of a captured command is sent
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureTo {
+    /// Replace the items in the window with a selectable item for each line
+    #[default]
+    List,
+    /// Print output to stdout and close the window
+    Stdout
+}
+
+/// Process options of menu item exec command
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ExecOptions {
+    /// Working directory of the command
+    pub cwd: Option<String>,
+    /// Environment variables added to the environment of waymenu
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Start with an empty environment, rather than the environment of waymenu
+    #[serde(default)]
+    pub clear_env: bool,
+    /// Text written to stdin of the command
+    pub stdin: Option<String>,
+    /// Wait for the command and capture its output
+    #[serde(default)]
+    pub capture: bool,
+    #[serde(default)]
+    pub capture_to: CaptureTo,
+    /// Seconds to wait for captured command
+    pub timeout: Option<u64>
+}
+
+impl ListItem {
+    pub fn from_json_reader<R: io::Read>(reader: R) -> io::Result<Vec<Self>> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list_items(json: &str) -> serde_json::Result<Vec<ListItem>> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn label_required_except_for_separators() {
+        assert!(list_items(r#"[{ "type": "separator" }]"#).is_ok());
+        assert!(list_items(r#"[{ "label": "a" }, { "type": "header", "label": "b" }]"#).is_ok());
+
+        let error = list_items(r#"[{ "exec": ["a"] }]"#).err().unwrap();
+        assert!(error.to_string().contains("`label` required for item entries"));
+
+        let error = list_items(r#"[{ "type": "header", "label": "" }]"#).err().unwrap();
+        assert!(error.to_string().contains("`label` required for header entries"));
+    }
+}
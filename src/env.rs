@@ -23,7 +23,7 @@ pub fn app_name() -> &'static str {
     env!("CARGO_PKG_NAME")
 }
 
-fn waymenu_home_dir() -> PathBuf {
+pub fn waymenu_home_dir() -> PathBuf {
     let home_dir = env::var("HOME")
         .expect("$HOME env var expected");
 
@@ -59,4 +59,151 @@ pub fn get_config_path() -> PathBuf {
 
 pub fn get_history_path() -> PathBuf {
     waymenu_state_dir().join("history")
-}
\ No newline at end of file
+}
+
+/// Get value of env var, with `$WAYMENU_HOME` defaulting to the config dir
+fn var(name: &str) -> Option<String> {
+    match env::var(name) {
+        Ok(v) => Some(v),
+        Err(..) if name == "WAYMENU_HOME" => Some(waymenu_home_dir().to_string_lossy().into_owned()),
+        Err(..) => None
+    }
+}
+
+/// Replace leading `~` with the home directory
+pub fn expand_home(value: &str) -> String {
+    let (home, rest) = split_home(value);
+    home + rest
+}
+
+/// Home directory when value starts with `~`, and the rest of the value
+fn split_home(value: &str) -> (String, &str) {
+    match (value.strip_prefix('~'), var("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => (home, rest),
+        _ => (String::new(), value)
+    }
+}
+
+/// Expand leading `~`, and `$VAR`, `${VAR}` or `${VAR:-default}` env vars in value,
+/// where `$$` is a literal `$`. References to unset env vars are left as is.
+pub fn expand_vars(value: &str) -> String {
+    let (mut expanded, rest) = split_home(value);
+
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        if chars.next_if_eq(&'$').is_some() {
+            expanded.push('$');
+        } else if chars.next_if_eq(&'{').is_some() {
+            let mut expr = String::new();
+            let mut depth = 1;
+            for c in chars.by_ref() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    break;
+                }
+                expr.push(c);
+            }
+
+            let value = match expr.split_once(":-") {
+                Some((name, default)) => var(name)
+                    .filter(|v| !v.is_empty())
+                    .unwrap_or_else(|| expand_vars(default)),
+                None => var(expr.as_str())
+                    .unwrap_or_else(|| format!("${{{expr}{}", if depth == 0 { "}" } else { "" }))
+            };
+            expanded.push_str(value.as_str());
+        } else {
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                name.push(c);
+            }
+
+            // `$` not followed by a name is kept as is
+            match var(name.as_str()).filter(|_| !name.is_empty()) {
+                Some(value) => expanded.push_str(value.as_str()),
+                None => {
+                    expanded.push('$');
+                    expanded.push_str(name.as_str());
+                }
+            }
+        }
+    }
+
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn home() -> String {
+        env::var("HOME").unwrap()
+    }
+
+    #[test]
+    fn expand_leading_tilde() {
+        assert_eq!(expand_vars("~"), home());
+        assert_eq!(expand_vars("~/a/b"), format!("{}/a/b", home()));
+        assert_eq!(expand_vars("~user/a"), "~user/a");
+        assert_eq!(expand_vars("a/~/b"), "a/~/b");
+        assert_eq!(expand_home("~/a/$B"), format!("{}/a/$B", home()));
+    }
+
+    #[test]
+    fn expand_var() {
+        env::set_var("WAYMENU_TEST_VAR", "value");
+        assert_eq!(expand_vars("$WAYMENU_TEST_VAR"), "value");
+        assert_eq!(expand_vars("a/$WAYMENU_TEST_VAR/b"), "a/value/b");
+        assert_eq!(expand_vars("$WAYMENU_TEST_VAR.css"), "value.css");
+    }
+
+    #[test]
+    fn expand_braced_var() {
+        env::set_var("WAYMENU_TEST_BRACED", "value");
+        assert_eq!(expand_vars("${WAYMENU_TEST_BRACED}"), "value");
+        assert_eq!(expand_vars("a${WAYMENU_TEST_BRACED}b"), "avalueb");
+    }
+
+    #[test]
+    fn expand_var_with_default() {
+        env::set_var("WAYMENU_TEST_DEFAULT", "value");
+        env::set_var("WAYMENU_TEST_EMPTY", "");
+        assert_eq!(expand_vars("${WAYMENU_TEST_DEFAULT:-other}"), "value");
+        assert_eq!(expand_vars("${WAYMENU_TEST_EMPTY:-other}"), "other");
+        assert_eq!(expand_vars("${WAYMENU_TEST_UNSET_DEFAULT:-other}"), "other");
+        assert_eq!(expand_vars("${WAYMENU_TEST_UNSET_DEFAULT:-$WAYMENU_TEST_DEFAULT/a}"), "value/a");
+        assert_eq!(expand_vars("${WAYMENU_TEST_UNSET_DEFAULT:-}"), "");
+    }
+
+    #[test]
+    fn unset_var_is_kept() {
+        assert_eq!(expand_vars("$WAYMENU_TEST_UNSET/a"), "$WAYMENU_TEST_UNSET/a");
+        assert_eq!(expand_vars("${WAYMENU_TEST_UNSET}/a"), "${WAYMENU_TEST_UNSET}/a");
+        assert_eq!(expand_vars("${WAYMENU_TEST_UNSET"), "${WAYMENU_TEST_UNSET");
+    }
+
+    #[test]
+    fn escaped_dollar() {
+        env::set_var("WAYMENU_TEST_ESCAPED", "value");
+        assert_eq!(expand_vars("$$WAYMENU_TEST_ESCAPED"), "$WAYMENU_TEST_ESCAPED");
+        assert_eq!(expand_vars("a$$b"), "a$b");
+        assert_eq!(expand_vars("$$$WAYMENU_TEST_ESCAPED"), "$value");
+        assert_eq!(expand_vars("a $ b$"), "a $ b$");
+    }
+
+    #[test]
+    fn waymenu_home_defaults_to_config_dir() {
+        if env::var("WAYMENU_HOME").is_err() {
+            assert_eq!(expand_vars("$WAYMENU_HOME/a"), format!("{}/a", waymenu_home_dir().to_string_lossy()));
+        }
+    }
+}
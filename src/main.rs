@@ -27,43 +27,131 @@ mod env;
 use cli::{Cli, Commands, Parser};
 
 
-fn main() -> io::Result<glib::ExitCode> {
+fn main() -> glib::ExitCode {
     let cli = Cli::parse();
 
     if cli.verbose {
         std::env::set_var("G_MESSAGES_DEBUG", env::app_name());
     }
 
+    // print errors with Display rather than Debug, config errors
+    // are reported one per line with the file path, line and column
+    run(cli).unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        glib::ExitCode::FAILURE
+    })
+}
+
+fn run(cli: Cli) -> io::Result<glib::ExitCode> {
     match cli.command.clone() {
-        Commands::InitConfig => {
-            write_config_defaults(&cli)?;
+        Commands::InitConfig { theme } => {
+            write_config_defaults(&cli, theme.unwrap_or_default())?;
             Ok(glib::ExitCode::SUCCESS)
         },
-        Commands::Launcher => {
+        Commands::PrintConfig { .. } => {
+            let (settings, sources) = cli.load_settings_with_sources()?;
+            println!("{}", settings.format_jsonc(&sources));
+            Ok(glib::ExitCode::SUCCESS)
+        },
+        Commands::CheckStyle => {
+            check_style(&cli)
+        },
+        Commands::CheckConfig => {
+            check_config(&cli)
+        },
+        Commands::Launcher { .. } => {
             let ctx = app::AppContext::with_app_list(cli)?;
             let app = app::App::new(ctx);
             Ok(app.start())
         },
+        Commands::OpenWith { files, set_default } => {
+            let ctx = app::AppContext::with_open_with(cli, files, set_default)?;
+            let app = app::App::new(ctx);
+            Ok(app.start())
+        },
         Commands::Menu { file } => {
             let ctx = app::AppContext::with_menu_list(cli, file)?;
             let app = app::App::new(ctx);
             Ok(app.start())
+        },
+        Commands::Prompt { .. } => {
+            let ctx = app::AppContext::with_prompt(cli)?;
+            let app = app::App::new(ctx);
+            Ok(app.start())
         }
     }
 }
 
-fn write_config_defaults(cli: &Cli) -> io::Result<()> {
+fn write_config_defaults(cli: &Cli, theme: config::Theme) -> io::Result<()> {
     let config_path = cli.get_config_path();
     write_file_if_not_exists(&config_path, include_bytes!("../assets/config.jsonc"))?;
     println!("Created {}", config_path.to_string_lossy());
 
     let style_path = cli.get_style_path();
-    write_file_if_not_exists(&style_path, include_bytes!("../assets/style.css"))?;
+    write_file_if_not_exists(&style_path, app::theme_css(theme).as_bytes())?;
     println!("Created {}", style_path.to_string_lossy());
 
     Ok(())
 }
 
+fn check_style(cli: &Cli) -> io::Result<glib::ExitCode> {
+    let settings = cli.load_settings()?;
+    let style_path = settings.style_path();
+    let stylesheet = app::Stylesheet::from_file(&style_path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", style_path.to_string_lossy())))?;
+
+    let mut stylesheets = vec![stylesheet];
+    for variant in ["dark", "light"] {
+        let variant_path = app::variant_path(&style_path, variant);
+        if variant_path.is_file() {
+            stylesheets.push(app::Stylesheet::from_file(&variant_path)?);
+        }
+    }
+    if let Some(extra_css) = &settings.extra_css {
+        for extra in app::Stylesheet::from_extra_css(extra_css) {
+            stylesheets.push(extra?);
+        }
+    }
+
+    let mut failed = false;
+    for stylesheet in stylesheets.iter() {
+        let errors = app::check_stylesheet(stylesheet);
+
+        if errors > 0 {
+            eprintln!("{}: {errors} error(s)", stylesheet.name);
+            failed = true;
+        } else {
+            println!("{}: ok", stylesheet.name);
+        }
+    }
+
+    if failed {
+        Ok(glib::ExitCode::FAILURE)
+    } else {
+        Ok(glib::ExitCode::SUCCESS)
+    }
+}
+
+fn check_config(cli: &Cli) -> io::Result<glib::ExitCode> {
+    let config_path = cli.get_config_path();
+    let config_name = config_path.to_string_lossy();
+
+    let problems = config::Settings::check(&config_path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{config_name}: {e}")))?;
+
+    for (file_path, problem) in problems.iter() {
+        eprintln!("{}:{problem}", file_path.to_string_lossy());
+    }
+
+    if problems.is_empty() {
+        println!("{config_name}: ok");
+        Ok(glib::ExitCode::SUCCESS)
+    } else {
+        eprintln!("{config_name}: {} error(s)", problems.len());
+        Ok(glib::ExitCode::FAILURE)
+    }
+}
+
 fn write_file_if_not_exists(file_path: &Path, content: &[u8]) -> io::Result<()> {
     if file_path.exists() {
         eprintln!("{} already exists, refusing to overwrite", file_path.to_string_lossy());